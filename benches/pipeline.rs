@@ -0,0 +1,97 @@
+//! Measures the scanner and the parser separately on generated Lox sources.
+//! Run with `cargo bench`, optionally passing a filter on the program name.
+
+use std::time::{Duration, Instant};
+
+use rlox::{parse::Parser, scanner::TokenStream, token::Token};
+
+const ITERATIONS: usize = 50;
+
+/// A long flat chain of mixed operators.
+fn arithmetic() -> String {
+    let operators = ["+", "-", "*", "/", "==", "<", ">=", "!="];
+    let mut source = String::from("1");
+    for (i, operator) in operators.iter().cycle().take(20_000).enumerate() {
+        source.push_str(&format!(" {operator} {}.5", i % 1000));
+    }
+    source
+}
+
+/// Deeply nested parentheses and unary operators.
+fn nested() -> String {
+    let depth = 200;
+    let mut source = String::new();
+    for _ in 0..100 {
+        source.push_str(&"(-".repeat(depth));
+        source.push_str("!true");
+        source.push_str(&")".repeat(depth));
+        source.push_str(" + ");
+    }
+    source.push_str("nil");
+    source
+}
+
+/// String concatenation spread over many lines, with comments.
+fn strings() -> String {
+    let mut source = String::from("\"\"");
+    for i in 0..100_000 {
+        source.push_str(&format!("\n  + \"line {i}\" // part {i}"));
+    }
+    source
+}
+
+fn scan(source: &str) -> Vec<Token<'_>> {
+    TokenStream::new(source)
+        .collect::<Result<_, _>>()
+        .expect("benchmark sources scan")
+}
+
+/// Runs `f` repeatedly and returns the median time of a run.
+fn measure<T>(mut f: impl FnMut() -> T) -> Duration {
+    let mut times = (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(f());
+            start.elapsed()
+        })
+        .collect::<Vec<_>>();
+    times.sort();
+    times[times.len() / 2]
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`, anything else filters the programs
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+    let programs = [
+        ("arithmetic", arithmetic as fn() -> String),
+        ("nested", nested),
+        ("strings", strings),
+    ];
+    println!(
+        "{:<12} {:>8} {:>12} {:>12}",
+        "program", "bytes", "scan", "parse"
+    );
+    for (name, program) in programs {
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !name.contains(filter.as_str()))
+        {
+            continue;
+        }
+        let source = program();
+        let tokens = scan(&source);
+        let scan_time = measure(|| scan(&source));
+        let parse_time = measure(|| {
+            Parser::new(tokens.clone())
+                .with_max_depth(usize::MAX)
+                .parse_expression()
+                .expect("benchmark sources parse")
+        });
+        println!(
+            "{name:<12} {:>8} {:>12?} {:>12?}",
+            source.len(),
+            scan_time,
+            parse_time
+        );
+    }
+}
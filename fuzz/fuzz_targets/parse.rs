@@ -0,0 +1,42 @@
+//! Parsing never panics, and every span it reports lies within the source.
+
+#![no_main]
+
+use std::ops::Range;
+
+use libfuzzer_sys::fuzz_target;
+use rlox::{
+    expression::{Expr, SideTable},
+    Error,
+};
+
+/// Every node of `expr` has a span within the source.
+fn check_spans(expr: &Expr, spans: &SideTable<Range<usize>>, source: &str) {
+    let span = spans.get(expr.id()).expect("every node has a span");
+    assert!(source.get(span.clone()).is_some());
+    match expr {
+        Expr::Grouping(v) => check_spans(&v.expr, spans, source),
+        Expr::Unary(v) => check_spans(&v.expr, spans, source),
+        Expr::Binary(v) => {
+            check_spans(&v.left, spans, source);
+            check_spans(&v.right, spans, source);
+        }
+        Expr::Literal(_) | Expr::Error(_) => {}
+    }
+}
+
+fuzz_target!(|source: &str| {
+    match rlox::compile(source) {
+        Ok(program) => check_spans(&program.expr, &program.spans, source),
+        Err(errors) => {
+            for error in errors {
+                let span = match &error {
+                    Error::Scan(error) => &error.span,
+                    Error::Parse(error) => &error.span,
+                };
+                assert!(source.get(span.clone()).is_some());
+                error.render(source);
+            }
+        }
+    }
+});
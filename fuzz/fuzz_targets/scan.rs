@@ -0,0 +1,19 @@
+//! Scanning never panics, and the tokens with their trivia cover the whole source.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rlox::scanner::TokenStream;
+
+fuzz_target!(|source: &str| {
+    let mut scanned = String::new();
+    for token in TokenStream::new(source).with_trivia().with_error_tokens() {
+        let token = token.expect("scan errors are returned as error tokens");
+        assert_eq!(source.get(token.span.clone()), Some(token.lexeme));
+        for trivia in &token.trivia {
+            scanned.push_str(trivia.text);
+        }
+        scanned.push_str(token.lexeme);
+    }
+    assert_eq!(scanned, source);
+});
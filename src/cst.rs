@@ -0,0 +1,170 @@
+//! A lossless concrete syntax tree. Unlike the AST it keeps every token, including
+//! parentheses and the end of input, so together with the tokens' trivia it
+//! reproduces the source exactly.
+
+use std::ops::Range;
+
+use crate::token::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyntaxKind {
+    Root,
+    Literal,
+    Grouping,
+    Unary,
+    Binary,
+    /// Tokens skipped while recovering from a parse error.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement<'a> {
+    Node(SyntaxNode<'a>),
+    Token(Token<'a>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode<'a> {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement<'a>>,
+}
+
+impl<'a> SyntaxNode<'a> {
+    /// All tokens of the node, in source order.
+    pub fn tokens(&self) -> Vec<&Token<'a>> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'n>(&'n self, tokens: &mut Vec<&'n Token<'a>>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// Source range of the node's tokens, without the leading trivia.
+    pub fn span(&self) -> Range<usize> {
+        let tokens = self.tokens();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => first.span.start..last.span.end,
+            _ => 0..0,
+        }
+    }
+
+    /// The source text of the node, including the trivia of its tokens.
+    pub fn text(&self) -> String {
+        self.tokens()
+            .into_iter()
+            .flat_map(|t| t.trivia.iter().map(|t| t.text).chain([t.lexeme]))
+            .collect()
+    }
+}
+
+/// Builds a tree from a sequence of node and token events, as emitted by the parser.
+#[derive(Default)]
+pub(crate) struct Builder<'a> {
+    stack: Vec<SyntaxNode<'a>>,
+}
+
+/// Position in the current node, at which a node can be started later.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Checkpoint(usize);
+
+impl<'a> Builder<'a> {
+    pub(crate) fn start_node(&mut self, kind: SyntaxKind) {
+        self.stack.push(SyntaxNode {
+            kind,
+            children: Vec::new(),
+        });
+    }
+
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.stack.last().map_or(0, |node| node.children.len()))
+    }
+
+    /// Starts a node that wraps everything added to the current node since `checkpoint`,
+    /// e.g. the left operand of a binary expression.
+    pub(crate) fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        let children = match self.stack.last_mut() {
+            Some(parent) => parent.children.split_off(checkpoint.0),
+            None => Vec::new(),
+        };
+        self.stack.push(SyntaxNode { kind, children });
+    }
+
+    pub(crate) fn token(&mut self, token: Token<'a>) {
+        if let Some(node) = self.stack.last_mut() {
+            node.children.push(SyntaxElement::Token(token));
+        }
+    }
+
+    pub(crate) fn finish_node(&mut self) {
+        let node = self
+            .stack
+            .pop()
+            .expect("finished a node that was not started");
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(SyntaxElement::Node(node)),
+            None => self.stack.push(node),
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> SyntaxNode<'a> {
+        assert_eq!(self.stack.len(), 1, "unbalanced syntax tree nodes");
+        self.stack.pop().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse::Parser, scanner::TokenStream};
+
+    use super::{SyntaxElement, SyntaxKind, SyntaxNode};
+
+    fn parse(source: &str) -> SyntaxNode<'_> {
+        let tokens = TokenStream::new(source)
+            .with_trivia()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        Parser::new(tokens).parse_syntax_tree().unwrap()
+    }
+
+    fn kinds(node: &SyntaxNode) -> String {
+        let children = node
+            .children
+            .iter()
+            .map(|child| match child {
+                SyntaxElement::Node(node) => kinds(node),
+                SyntaxElement::Token(token) => format!("'{}'", token.lexeme),
+            })
+            .collect::<Vec<_>>();
+        format!("{:?}[{}]", node.kind, children.join(" "))
+    }
+
+    #[test]
+    fn structure() {
+        let tree = parse("-(1 + 2) * 3");
+        assert_eq!(
+            kinds(&tree),
+            "Root[Binary[Unary['-' Grouping['(' Binary[Literal['1'] '+' Literal['2']] ')']] '*' Literal['3']] '']"
+        );
+    }
+
+    #[test]
+    fn lossless() {
+        let source = "  // leading comment\n(1 +  2)\n  *  -3 // trailing\n";
+        let tree = parse(source);
+        assert_eq!(tree.text(), source);
+
+        let SyntaxElement::Node(expr) = &tree.children[0] else {
+            panic!("expected an expression node")
+        };
+        assert_eq!(expr.kind, SyntaxKind::Binary);
+        assert_eq!(expr.span(), 21..37);
+        assert_eq!(&source[expr.span()], "(1 +  2)\n  *  -3");
+    }
+}
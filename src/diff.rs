@@ -0,0 +1,88 @@
+//! Structural comparison of ASTs. Unlike a textual diff it ignores layout,
+//! comments and parentheses, and reports only changes to the meaning.
+
+use crate::expression::Expr;
+
+/// The smallest subtrees that differ between the old and the new tree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Change<'e> {
+    pub old: &'e Expr,
+    pub new: &'e Expr,
+}
+
+/// Compares the trees, returning their differing subtrees in source order.
+/// Error nodes never match, as it is unknown what they stand for.
+pub fn diff<'e>(old: &'e Expr, new: &'e Expr) -> Vec<Change<'e>> {
+    let mut changes = Vec::new();
+    diff_into(old, new, &mut changes);
+    changes
+}
+
+fn diff_into<'e>(old: &'e Expr, new: &'e Expr, changes: &mut Vec<Change<'e>>) {
+    let (old, new) = (old.without_grouping(), new.without_grouping());
+    match (old, new) {
+        (Expr::Unary(a), Expr::Unary(b)) if a.unary == b.unary => {
+            diff_into(&a.expr, &b.expr, changes);
+        }
+        (Expr::Binary(a), Expr::Binary(b)) if a.operator == b.operator => {
+            diff_into(&a.left, &b.left, changes);
+            diff_into(&a.right, &b.right, changes);
+        }
+        (Expr::Literal(a), Expr::Literal(b)) if a.value == b.value => {}
+        _ => changes.push(Change { old, new }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{expression::Expr, parse::Parser, pretty_printing::AstPrint, scanner::scan_tokens};
+
+    use super::diff;
+
+    fn parse(source: &str) -> Expr {
+        Parser::new(scan_tokens(source).unwrap())
+            .with_recovery()
+            .parse_expression()
+            .unwrap()
+    }
+
+    fn changes(old: &str, new: &str) -> Vec<(String, String)> {
+        let (old, new) = (parse(old), parse(new));
+        diff(&old, &new)
+            .into_iter()
+            .map(|change| (change.old.print_ast(), change.new.print_ast()))
+            .collect()
+    }
+
+    #[test]
+    fn same() {
+        assert_eq!(changes("1 + 2 * 3", "1+2*3 // comment"), vec![]);
+        assert_eq!(changes("(1 + (2 * 3))", "1 + 2 * 3"), vec![]);
+    }
+
+    #[test]
+    fn changed() {
+        assert_eq!(
+            changes("1 + 2 * 3", "1 + 2 * 4"),
+            vec![("3".to_string(), "4".to_string())]
+        );
+        assert_eq!(
+            changes("-1 + 2 * 3", "!1 + 2 / 3"),
+            vec![
+                ("(- 1)".to_string(), "(! 1)".to_string()),
+                ("(* 2 3)".to_string(), "(/ 2 3)".to_string())
+            ]
+        );
+        assert_eq!(
+            changes("(1 + 2) * 3", "1 + 2 * 3"),
+            vec![(
+                "(* (group (+ 1 2)) 3)".to_string(),
+                "(+ 1 (* 2 3))".to_string()
+            )]
+        );
+        assert_eq!(
+            changes("1 + )", "1 + )"),
+            vec![("<error>".to_string(), "<error>".to_string())]
+        );
+    }
+}
@@ -1,14 +1,47 @@
-/// expression  → literal
-///             | unary
-///             | binary
-///             | grouping ;
-///
-/// literal     → NUMBER | STRING | "true" | "false" | "nil" ;
-/// grouping    → "(" expression ")" ;
-/// unary       → ( "-" | "!" ) expression ;
-/// binary      → expression operator expression ;
-/// operator    → "==" | "!=" | "<" | "<=" | ">" | ">=" | "+"  | "-"  | "*" | "/" ;
+//! expression  → literal
+//!             | unary
+//!             | binary
+//!             | grouping ;
+//!
+//! literal     → NUMBER | STRING | "true" | "false" | "nil" ;
+//! grouping    → "(" expression ")" ;
+//! unary       → ( "-" | "!" ) expression ;
+//! binary      → expression operator expression ;
+//! operator    → "==" | "!=" | "<" | "<=" | ">" | ">=" | "+"  | "-"  | "*" | "/" ;
 
+/// Identifies a node within the AST it was parsed into.
+/// Ids are assigned by the parser, in the order the nodes are created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct NodeId(pub usize);
+
+/// Information about AST nodes kept outside of the tree, keyed by node id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SideTable<T> {
+    entries: Vec<Option<T>>,
+}
+
+impl<T> Default for SideTable<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<T> SideTable<T> {
+    pub fn insert(&mut self, id: NodeId, value: T) {
+        if id.0 >= self.entries.len() {
+            self.entries.resize_with(id.0 + 1, || None);
+        }
+        self.entries[id.0] = Some(value);
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.entries.get(id.0).and_then(Option::as_ref)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Nil,
     Bool(bool),
@@ -16,7 +49,7 @@ pub enum Literal {
     String(String),
 }
 
-#[derive(parse_display::Display)]
+#[derive(Debug, Clone, Copy, PartialEq, parse_display::Display)]
 pub enum Unary {
     #[display("!")]
     Bang,
@@ -24,14 +57,38 @@ pub enum Unary {
     Minus,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Grouping(GroupingExpr),
     Unary(UnaryExpr),
     Binary(BinaryExpr),
-    Literal(Literal),
+    Literal(LiteralExpr),
+    /// Placeholder for an expression that failed to parse.
+    Error(NodeId),
+}
+
+impl Expr {
+    pub fn id(&self) -> NodeId {
+        match self {
+            Expr::Grouping(v) => v.id,
+            Expr::Unary(v) => v.id,
+            Expr::Binary(v) => v.id,
+            Expr::Literal(v) => v.id,
+            Expr::Error(id) => *id,
+        }
+    }
+
+    /// The expression inside any parentheses around it.
+    pub fn without_grouping(&self) -> &Expr {
+        let mut expr = self;
+        while let Expr::Grouping(v) = expr {
+            expr = &v.expr;
+        }
+        expr
+    }
 }
 
-#[derive(parse_display::Display)]
+#[derive(Debug, Clone, Copy, PartialEq, parse_display::Display)]
 pub enum Operator {
     #[display(">")]
     Greater,
@@ -55,17 +112,29 @@ pub enum Operator {
     Multiply,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct BinaryExpr {
+    pub id: NodeId,
     pub left: Box<Expr>,
     pub operator: Operator,
     pub right: Box<Expr>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct UnaryExpr {
+    pub id: NodeId,
     pub unary: Unary,
     pub expr: Box<Expr>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct GroupingExpr {
+    pub id: NodeId,
     pub expr: Box<Expr>,
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiteralExpr {
+    pub id: NodeId,
+    pub value: Literal,
+}
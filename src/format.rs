@@ -0,0 +1,223 @@
+//! A formatter that reprints source in a canonical layout. It works on the
+//! lossless syntax tree, so comments survive formatting.
+//!
+//! Expressions that fit are printed on one line, with single spaces around
+//! binary operators. Longer ones are broken after binary operators and inside
+//! parentheses, with continuation lines indented by four spaces.
+
+use crate::{
+    cst::{SyntaxElement, SyntaxKind, SyntaxNode},
+    token::{Token, TriviaKind},
+};
+
+/// Lines longer than this are wrapped where the syntax allows it.
+pub const MAX_WIDTH: usize = 80;
+const INDENT: &str = "    ";
+
+/// Formats a tree parsed from the whole source, see `Parser::parse_syntax_tree`.
+/// The tokens need to be scanned with trivia, or comments are lost.
+pub fn format(tree: &SyntaxNode<'_>) -> String {
+    let mut formatter = Formatter::default();
+    formatter.node(tree, 0, 0);
+    let mut output = formatter.output;
+    output.truncate(output.trim_end().len());
+    output.push('\n');
+    output
+}
+
+/// The node printed on a single line, or `None` if it has comments and can't be.
+fn flat(node: &SyntaxNode<'_>) -> Option<String> {
+    let mut output = String::new();
+    for (i, child) in node.children.iter().enumerate() {
+        if i > 0 && matches!(node.kind, SyntaxKind::Binary | SyntaxKind::Error) {
+            output.push(' ');
+        }
+        match child {
+            SyntaxElement::Node(node) => output.push_str(&flat(node)?),
+            SyntaxElement::Token(token) => {
+                if token
+                    .trivia
+                    .iter()
+                    .any(|t| t.kind != TriviaKind::Whitespace)
+                {
+                    return None;
+                }
+                output.push_str(token.lexeme);
+            }
+        }
+    }
+    Some(output)
+}
+
+#[derive(Default)]
+struct Formatter {
+    output: String,
+}
+
+impl Formatter {
+    /// Prints the node, leaving room for `reserve` more characters at the end of its last line.
+    fn node(&mut self, node: &SyntaxNode<'_>, indent: usize, reserve: usize) {
+        if let Some(flat) = flat(node) {
+            if self.column() + flat.chars().count() + reserve <= MAX_WIDTH {
+                self.output.push_str(&flat);
+                return;
+            }
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            let indent = match (node.kind, i) {
+                (SyntaxKind::Binary, 1) | (SyntaxKind::Error, 1..) => {
+                    self.space();
+                    indent
+                }
+                // Break after the operator, so that it ends the line
+                (SyntaxKind::Binary, 2) | (SyntaxKind::Grouping, 1) => {
+                    self.newline(indent + 1);
+                    indent + 1
+                }
+                (SyntaxKind::Grouping, 2) => {
+                    self.newline(indent);
+                    indent
+                }
+                _ => indent,
+            };
+            let reserve = match (node.kind, node.children.get(i + 1)) {
+                // The left operand is followed by the operator on the same line
+                (SyntaxKind::Binary, Some(SyntaxElement::Token(operator))) => {
+                    1 + operator.lexeme.chars().count()
+                }
+                (_, None) => reserve,
+                _ => 0,
+            };
+            match child {
+                SyntaxElement::Node(node) => self.node(node, indent, reserve),
+                SyntaxElement::Token(token) => self.token(token, indent),
+            }
+        }
+    }
+
+    fn token(&mut self, token: &Token<'_>, indent: usize) {
+        // Whether the comments start on their own line, or follow the previous token
+        let mut own_line = self.output.is_empty();
+        for trivia in &token.trivia {
+            match trivia.kind {
+                TriviaKind::Whitespace => own_line |= trivia.text.contains('\n'),
+                TriviaKind::Comment | TriviaKind::Shebang => {
+                    if own_line {
+                        self.newline(indent);
+                    } else {
+                        // Keep trailing comments on the line of the previous token
+                        self.output.truncate(self.output.trim_end().len());
+                        self.space();
+                    }
+                    self.output.push_str(trivia.text.trim_end());
+                    // Line comments run until the end of the line
+                    self.newline(indent);
+                    own_line = true;
+                }
+                TriviaKind::Skipped => {
+                    self.space();
+                    self.output.push_str(trivia.text);
+                }
+            }
+        }
+        self.output.push_str(token.lexeme);
+    }
+
+    fn line_start(&self) -> usize {
+        self.output.rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn column(&self) -> usize {
+        self.output[self.line_start()..].chars().count()
+    }
+
+    /// Starts a new line, unless the current one is still empty.
+    fn newline(&mut self, indent: usize) {
+        let line_start = self.line_start();
+        if self.output[line_start..].trim().is_empty() {
+            self.output.truncate(line_start);
+        } else {
+            self.output.truncate(self.output.trim_end().len());
+            self.output.push('\n');
+        }
+        self.output.push_str(&INDENT.repeat(indent));
+    }
+
+    fn space(&mut self) {
+        if !self.output[self.line_start()..].trim().is_empty() && !self.output.ends_with(' ') {
+            self.output.push(' ');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse::Parser, scanner::TokenStream};
+
+    use super::format;
+
+    fn fmt(source: &str) -> String {
+        let tokens = TokenStream::new(source)
+            .with_trivia()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let tree = Parser::new(tokens).parse_syntax_tree().unwrap();
+        let formatted = format(&tree);
+        assert_eq!(
+            fmt_again(&formatted),
+            formatted,
+            "formatting is not idempotent"
+        );
+        formatted
+    }
+
+    fn fmt_again(source: &str) -> String {
+        let tokens = TokenStream::new(source)
+            .with_trivia()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        format(&Parser::new(tokens).parse_syntax_tree().unwrap())
+    }
+
+    #[test]
+    fn spacing() {
+        assert_eq!(fmt("1+2*3"), "1 + 2 * 3\n");
+        assert_eq!(fmt("  ( 1+2 )  *- 3\n\n"), "(1 + 2) * -3\n");
+        assert_eq!(fmt("!\ttrue==\"a  b\""), "!true == \"a  b\"\n");
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(fmt("1+2 // sum  "), "1 + 2 // sum\n");
+        assert_eq!(
+            fmt("// leading\n1 + // one\n  2"),
+            "// leading\n1 + // one\n    2\n"
+        );
+        assert_eq!(
+            fmt("#!/usr/bin/env rlox\n(1 // one\n)"),
+            "#!/usr/bin/env rlox\n(\n    1 // one\n)\n"
+        );
+    }
+
+    #[test]
+    fn wrapping() {
+        let long = ["1234567890"; 10].join("+");
+        assert_eq!(
+            fmt(&long),
+            format!(
+                "{} +\n    1234567890 +\n    1234567890 +\n    1234567890 +\n    1234567890\n",
+                ["1234567890"; 6].join(" + ")
+            )
+        );
+
+        let grouped = format!("-({long})");
+        assert_eq!(
+            fmt(&grouped),
+            format!(
+                "-(\n    {} +{}\n        1234567890\n)\n",
+                ["1234567890"; 5].join(" + "),
+                "\n        1234567890 +".repeat(4)
+            )
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Syntax highlighting, driven by the token stream so that it matches what the
+//! scanner sees. Source that fails to scan is still highlighted, as errors.
+
+use crate::{
+    scanner::TokenStream,
+    token::{TokenType, TriviaKind},
+};
+
+/// What a piece of source is highlighted as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Keyword,
+    /// `true`, `false` and `nil`.
+    Literal,
+    Number,
+    String,
+    Operator,
+    Comment,
+    Error,
+}
+
+/// Splits the source into pieces with the style each is highlighted with, if any.
+/// Together the pieces make up the whole source.
+pub fn classify(source: &str) -> Vec<(Option<Style>, &str)> {
+    let mut pieces = Vec::new();
+    let tokens = TokenStream::new(source)
+        .with_trivia()
+        .with_error_tokens()
+        .map(|token| token.expect("error tokens are emitted instead of errors"));
+    for token in tokens {
+        for trivia in &token.trivia {
+            let style = match trivia.kind {
+                TriviaKind::Whitespace => None,
+                TriviaKind::Comment | TriviaKind::Shebang => Some(Style::Comment),
+                TriviaKind::Skipped => Some(Style::Error),
+            };
+            pieces.push((style, trivia.text));
+        }
+        if !token.lexeme.is_empty() {
+            pieces.push((style(&token.ty), token.lexeme));
+        }
+    }
+    pieces
+}
+
+fn style(ty: &TokenType) -> Option<Style> {
+    let style = match ty {
+        TokenType::LeftParen
+        | TokenType::RightParen
+        | TokenType::LeftBrace
+        | TokenType::RightBrace
+        | TokenType::Comma
+        | TokenType::Dot
+        | TokenType::Semicolon
+        | TokenType::Identifier(_)
+        | TokenType::Eof => return None,
+        TokenType::Minus
+        | TokenType::Plus
+        | TokenType::Slash
+        | TokenType::Star
+        | TokenType::Bang
+        | TokenType::BangEqual
+        | TokenType::Equal
+        | TokenType::EqualEqual
+        | TokenType::Greater
+        | TokenType::GreaterEqual
+        | TokenType::Less
+        | TokenType::LessEqual => Style::Operator,
+        TokenType::Number(_) => Style::Number,
+        TokenType::String => Style::String,
+        TokenType::True | TokenType::False | TokenType::Nil => Style::Literal,
+        TokenType::And
+        | TokenType::Class
+        | TokenType::Else
+        | TokenType::Fun
+        | TokenType::For
+        | TokenType::If
+        | TokenType::Or
+        | TokenType::Print
+        | TokenType::Return
+        | TokenType::Super
+        | TokenType::This
+        | TokenType::Var
+        | TokenType::While => Style::Keyword,
+        TokenType::Error(_) => Style::Error,
+    };
+    Some(style)
+}
+
+/// Highlights the source with ANSI escape codes, for printing to a terminal.
+pub fn highlight_ansi(source: &str) -> String {
+    let mut output = String::new();
+    for (style, text) in classify(source) {
+        let code = match style {
+            None => {
+                output.push_str(text);
+                continue;
+            }
+            Some(Style::Keyword) => "35",
+            Some(Style::Literal) | Some(Style::Number) => "36",
+            Some(Style::String) => "32",
+            Some(Style::Operator) => "33",
+            Some(Style::Comment) => "90",
+            Some(Style::Error) => "4;31",
+        };
+        output.push_str("\x1b[");
+        output.push_str(code);
+        output.push('m');
+        output.push_str(text);
+        output.push_str("\x1b[0m");
+    }
+    output
+}
+
+/// Highlights the source as HTML, a `<pre>` element with a `<span>` for each styled piece.
+/// The spans' classes are styled by `STYLESHEET`.
+pub fn highlight_html(source: &str) -> String {
+    let mut output = String::from("<pre class=\"lox\"><code>");
+    for (style, text) in classify(source) {
+        let class = match style {
+            None => {
+                escape_html(text, &mut output);
+                continue;
+            }
+            Some(Style::Keyword) => "keyword",
+            Some(Style::Literal) => "literal",
+            Some(Style::Number) => "number",
+            Some(Style::String) => "string",
+            Some(Style::Operator) => "operator",
+            Some(Style::Comment) => "comment",
+            Some(Style::Error) => "error",
+        };
+        output.push_str("<span class=\"");
+        output.push_str(class);
+        output.push_str("\">");
+        escape_html(text, &mut output);
+        output.push_str("</span>");
+    }
+    output.push_str("</code></pre>\n");
+    output
+}
+
+/// The default styles for the output of `highlight_html`.
+pub const STYLESHEET: &str = "\
+.lox .keyword { color: #a626a4; }
+.lox .literal, .lox .number { color: #0184bc; }
+.lox .string { color: #50a14f; }
+.lox .operator { color: #c18401; }
+.lox .comment { color: #a0a1a7; font-style: italic; }
+.lox .error { color: #e45649; text-decoration: wavy underline; }
+";
+
+fn escape_html(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            c => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, highlight_ansi, highlight_html, Style};
+
+    #[test]
+    fn pieces() {
+        assert_eq!(
+            classify("-(1 + x) // and\n@ \"s\" or nil"),
+            vec![
+                (Some(Style::Operator), "-"),
+                (None, "("),
+                (Some(Style::Number), "1"),
+                (None, " "),
+                (Some(Style::Operator), "+"),
+                (None, " "),
+                (None, "x"),
+                (None, ")"),
+                (None, " "),
+                (Some(Style::Comment), "// and"),
+                (None, "\n"),
+                (Some(Style::Error), "@"),
+                (None, " "),
+                (Some(Style::String), "\"s\""),
+                (None, " "),
+                (Some(Style::Keyword), "or"),
+                (None, " "),
+                (Some(Style::Literal), "nil"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lossless() {
+        let source = "#!/usr/bin/env rlox\nvar x = 0x1F; // comment\n\"unterminated\n";
+        let text = classify(source)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<String>();
+        assert_eq!(text, source);
+    }
+
+    #[test]
+    fn ansi() {
+        assert_eq!(
+            highlight_ansi("1 + x"),
+            "\x1b[36m1\x1b[0m \x1b[33m+\x1b[0m x"
+        );
+    }
+
+    #[test]
+    fn html() {
+        assert_eq!(
+            highlight_html("1 < \"a&b\" // <"),
+            "<pre class=\"lox\"><code><span class=\"number\">1</span> \
+             <span class=\"operator\">&lt;</span> \
+             <span class=\"string\">&quot;a&amp;b&quot;</span> \
+             <span class=\"comment\">// &lt;</span></code></pre>\n"
+        );
+    }
+}
@@ -1,4 +1,248 @@
-mod expression;
-mod pretty_printing;
+pub mod cst;
+pub mod diff;
+pub mod expression;
+pub mod format;
+pub mod highlight;
+pub mod lint;
+pub mod parse;
+pub mod pretty_printing;
+#[cfg(test)]
+mod random;
+pub mod repl;
 pub mod scanner;
-mod token;
+pub mod sexpr;
+pub mod symbol;
+pub mod token;
+pub mod unparse;
+
+use std::{fmt, ops::Range};
+
+use expression::{Expr, SideTable};
+use parse::{ParseError, Parser, DEFAULT_MAX_DEPTH};
+use scanner::{ScanError, TokenStream};
+use symbol::Interner;
+use token::Token;
+
+/// An error in Lox source code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Scan(ScanError),
+    Parse(ParseError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Scan(error) => error.fmt(f),
+            Error::Parse(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Renders the error, followed by the source line it points at.
+    /// The end of input after a trailing line break is pointed at past the
+    /// end of the error's line.
+    pub fn render(&self, source: &str) -> String {
+        let (span, line_no) = match self {
+            Error::Scan(error) => (&error.span, error.line),
+            Error::Parse(error) => (&error.span, error.line),
+        };
+        let start = source
+            .split_inclusive('\n')
+            .take(line_no)
+            .map(str::len)
+            .sum::<usize>();
+        let end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line = source[start..end].trim_end_matches('\r');
+        let from = span.start.clamp(start, start + line.len());
+        let to = span.end.clamp(from, start + line.len());
+        let column = source[start..from].chars().count();
+        let width = source[from..to].chars().count().max(1);
+        format!(
+            "{self}\n    {line}\n    {}{}",
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// Source code that compiled without errors.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub expr: Expr,
+    /// Source spans of the nodes, see `Parser::spans`.
+    pub spans: SideTable<Range<usize>>,
+}
+
+/// Scanned source, the first phase of `compile`.
+#[derive(Debug, Clone)]
+pub struct Scanned<'a> {
+    pub tokens: Vec<Token<'a>>,
+    pub errors: Vec<Error>,
+    /// Resolves the symbols of identifier tokens.
+    pub interner: Interner,
+}
+
+/// Scans the source, collecting every error instead of stopping at the first.
+pub fn scan(source: &str) -> Scanned<'_> {
+    let mut errors = Vec::new();
+    let mut stream = TokenStream::new(source);
+    let tokens = stream
+        .by_ref()
+        .filter_map(|token| token.map_err(|error| errors.push(Error::Scan(error))).ok())
+        .collect();
+    Scanned {
+        tokens,
+        errors,
+        interner: stream.into_interner(),
+    }
+}
+
+impl Scanned<'_> {
+    /// Parses the tokens, failing with the scan and parse errors if there are any.
+    pub fn parse(self) -> Result<Program, Vec<Error>> {
+        let mut errors = self.errors;
+        let mut parser = Parser::new(self.tokens).with_recovery();
+        let result = parser.parse_expression();
+        errors.extend(parser.errors().iter().cloned().map(Error::Parse));
+        match result {
+            Ok(expr) if errors.is_empty() => Ok(Program {
+                expr,
+                spans: parser.spans().clone(),
+            }),
+            Ok(_) => Err(errors),
+            Err(error) => {
+                errors.push(Error::Parse(error));
+                Err(errors)
+            }
+        }
+    }
+}
+
+/// Scans and parses the source, collecting every error instead of stopping at the first.
+pub fn compile(source: &str) -> Result<Program, Vec<Error>> {
+    compile_with(source, Limits::default())
+}
+
+/// Limits on the input `compile_with` accepts, see `Parser::with_max_depth`
+/// and `Parser::with_budget`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_depth: usize,
+    pub max_tokens: usize,
+    pub max_nodes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_tokens: usize::MAX,
+            max_nodes: usize::MAX,
+        }
+    }
+}
+
+/// Like `compile`, but within the given limits. Tokens are scanned as the
+/// parser needs them, so input past the token budget is never scanned.
+pub fn compile_with(source: &str, limits: Limits) -> Result<Program, Vec<Error>> {
+    let mut errors = Vec::new();
+    let tokens = TokenStream::new(source)
+        .filter_map(|token| token.map_err(|error| errors.push(Error::Scan(error))).ok());
+    let mut parser = Parser::new(tokens)
+        .with_recovery()
+        .with_max_depth(limits.max_depth)
+        .with_budget(limits.max_tokens, limits.max_nodes);
+    let result = parser.parse_expression();
+    let parse_errors = parser.errors().to_vec();
+    let spans = parser.spans().clone();
+    drop(parser);
+
+    errors.extend(parse_errors.into_iter().map(Error::Parse));
+    match result {
+        Ok(expr) if errors.is_empty() => Ok(Program { expr, spans }),
+        Ok(_) => Err(errors),
+        Err(error) => {
+            errors.push(Error::Parse(error));
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compile, compile_with, parse::ParseErrorKind, pretty_printing::AstPrint, Error, Limits,
+    };
+
+    #[test]
+    fn compiles() {
+        let program = compile("1 + 2 * 3").unwrap();
+        assert_eq!(program.expr.print_ast(), "(+ 1 (* 2 3))");
+        assert_eq!(program.spans.get(program.expr.id()), Some(&(0..9)));
+    }
+
+    #[test]
+    fn reports_every_error() {
+        let source = "1 +\n(2 @ 3";
+        let errors = compile(source).unwrap_err();
+        let rendered = errors
+            .iter()
+            .map(|error| error.render(source))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            [
+                "[line 2] Error: Invalid character: '@'\n    (2 @ 3\n       ^",
+                "[line 2] Error: expected operator or ')' after expression, found '3'; \
+                 unclosed '(' opened at line 2\n    (2 @ 3\n         ^",
+            ]
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let kinds = |source, limits| {
+            compile_with(source, limits)
+                .unwrap_err()
+                .into_iter()
+                .map(|error| match error {
+                    Error::Parse(error) => error.kind,
+                    Error::Scan(error) => panic!("unexpected {error}"),
+                })
+                .collect::<Vec<_>>()
+        };
+        let limits = Limits {
+            max_depth: 2,
+            ..Limits::default()
+        };
+        assert!(compile_with("(1)", limits).is_ok());
+        assert_eq!(kinds("((1))", limits), [ParseErrorKind::TooDeeplyNested]);
+        // The invalid character is past the budget and never scanned
+        let limits = Limits {
+            max_tokens: 3,
+            ..Limits::default()
+        };
+        assert_eq!(
+            kinds("1 + 2 + @", limits),
+            [ParseErrorKind::TooManyTokens { limit: 3 }]
+        );
+    }
+
+    #[test]
+    fn renders_end_of_input() {
+        for source in ["1 +\n", "1 +\r\n", "1 +"] {
+            let errors = compile(source).unwrap_err();
+            assert_eq!(
+                errors[0].render(source),
+                "[line 1] Error: expected expression, found end of input\n    1 +\n       ^",
+                "{source:?}"
+            );
+        }
+    }
+}
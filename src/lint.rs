@@ -0,0 +1,213 @@
+//! Lints: checks for code that is valid, but likely a mistake or needlessly complicated.
+
+use std::{collections::HashMap, ops::Range};
+
+use crate::{
+    diff::diff,
+    expression::{Expr, NodeId, Operator, SideTable},
+};
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, parse_display::Display, parse_display::FromStr,
+)]
+#[display(style = "kebab-case")]
+pub enum Rule {
+    /// Comparing or subtracting an expression from itself, e.g. `x == x`.
+    IdenticalOperands,
+    /// Applying `!` or `-` twice in a row, e.g. `!!x`.
+    DoubleNegation,
+    /// Parentheses around a literal, a grouping or the whole expression.
+    RedundantParentheses,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 3] = [
+        Rule::IdenticalOperands,
+        Rule::DoubleNegation,
+        Rule::RedundantParentheses,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, parse_display::Display)]
+pub enum Level {
+    #[display("allow")]
+    Allow,
+    #[display("warning")]
+    Warn,
+    #[display("error")]
+    Deny,
+}
+
+/// The level of each rule. Rules that are not configured warn.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Rule, Level>,
+}
+
+impl LintConfig {
+    pub fn with_level(mut self, rule: Rule, level: Level) -> Self {
+        self.levels.insert(rule, level);
+        self
+    }
+
+    pub fn level(&self, rule: Rule) -> Level {
+        self.levels.get(&rule).copied().unwrap_or(Level::Warn)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub rule: Rule,
+    pub level: Level,
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+/// Checks the expression against all rules that are not allowed.
+/// `spans` are the node spans recorded by the parser, see `Parser::spans`.
+pub fn lint(expr: &Expr, spans: &SideTable<Range<usize>>, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        spans,
+        config,
+        diagnostics: Vec::new(),
+    };
+    let mut expr = expr;
+    while let Expr::Grouping(v) = expr {
+        linter.report(
+            Rule::RedundantParentheses,
+            v.id,
+            "unnecessary parentheses around the whole expression",
+        );
+        expr = &v.expr;
+    }
+    linter.expr(expr);
+    linter.diagnostics
+}
+
+struct Linter<'l> {
+    spans: &'l SideTable<Range<usize>>,
+    config: &'l LintConfig,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Grouping(v) => {
+                if let Expr::Literal(_) | Expr::Grouping(_) = &*v.expr {
+                    self.report(Rule::RedundantParentheses, v.id, "unnecessary parentheses");
+                }
+                self.expr(&v.expr);
+            }
+            Expr::Unary(v) => {
+                if let Expr::Unary(inner) = v.expr.without_grouping() {
+                    if inner.unary == v.unary {
+                        let message = format!("'{}' is applied twice", v.unary);
+                        self.report(Rule::DoubleNegation, v.id, &message);
+                    }
+                }
+                self.expr(&v.expr);
+            }
+            Expr::Binary(v) => {
+                let compares = !matches!(v.operator, Operator::Plus | Operator::Multiply);
+                if compares && diff(&v.left, &v.right).is_empty() {
+                    let message = format!("both operands of '{}' are the same", v.operator);
+                    self.report(Rule::IdenticalOperands, v.id, &message);
+                }
+                self.expr(&v.left);
+                self.expr(&v.right);
+            }
+            Expr::Literal(_) | Expr::Error(_) => {}
+        }
+    }
+
+    fn report(&mut self, rule: Rule, id: NodeId, message: &str) {
+        let level = self.config.level(rule);
+        if level == Level::Allow {
+            return;
+        }
+        self.diagnostics.push(Diagnostic {
+            rule,
+            level,
+            span: self.spans.get(id).cloned().unwrap_or_default(),
+            message: message.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse::Parser, scanner::scan_tokens};
+
+    use super::{lint, Level, LintConfig, Rule};
+
+    fn lint_source<'s>(source: &'s str, config: &LintConfig) -> Vec<(Rule, Level, &'s str)> {
+        let mut parser = Parser::new(scan_tokens(source).unwrap());
+        let expr = parser.parse_expression().unwrap();
+        lint(&expr, parser.spans(), config)
+            .into_iter()
+            .map(|d| (d.rule, d.level, &source[d.span]))
+            .collect()
+    }
+
+    #[test]
+    fn rules() {
+        let config = LintConfig::default();
+        assert_eq!(lint_source("1 + 2 * 3", &config), vec![]);
+        assert_eq!(
+            lint_source("(1 + (2 - 2)) == 1 + (2 - 2)", &config),
+            vec![
+                (
+                    Rule::IdenticalOperands,
+                    Level::Warn,
+                    "(1 + (2 - 2)) == 1 + (2 - 2)"
+                ),
+                (Rule::IdenticalOperands, Level::Warn, "2 - 2"),
+                (Rule::IdenticalOperands, Level::Warn, "2 - 2"),
+            ]
+        );
+        assert_eq!(lint_source("2 * 2 + \"a\" + \"a\"", &config), vec![]);
+        assert_eq!(
+            lint_source("!(!true) == -1", &config),
+            vec![(Rule::DoubleNegation, Level::Warn, "!(!true)")]
+        );
+        assert_eq!(lint_source("!-1", &config), vec![]);
+        assert_eq!(
+            lint_source("((1 + 2))", &config),
+            vec![
+                (Rule::RedundantParentheses, Level::Warn, "((1 + 2))"),
+                (Rule::RedundantParentheses, Level::Warn, "(1 + 2)"),
+            ]
+        );
+        assert_eq!(
+            lint_source("((1))", &config),
+            vec![
+                (Rule::RedundantParentheses, Level::Warn, "((1))"),
+                (Rule::RedundantParentheses, Level::Warn, "(1)"),
+            ]
+        );
+        assert_eq!(
+            lint_source("(1) * (2 + 3)", &config),
+            vec![(Rule::RedundantParentheses, Level::Warn, "(1)")]
+        );
+    }
+
+    #[test]
+    fn levels() {
+        let config = LintConfig::default()
+            .with_level(Rule::RedundantParentheses, Level::Allow)
+            .with_level(Rule::IdenticalOperands, Level::Deny);
+        assert_eq!(
+            lint_source("(1) != (1)", &config),
+            vec![(Rule::IdenticalOperands, Level::Deny, "(1) != (1)")]
+        );
+    }
+
+    #[test]
+    fn rule_names() {
+        assert_eq!(Rule::IdenticalOperands.to_string(), "identical-operands");
+        for rule in Rule::ALL {
+            assert_eq!(rule.to_string().parse::<Rule>().unwrap(), rule);
+        }
+    }
+}
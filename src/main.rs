@@ -1,53 +1,481 @@
-use std::{io::Write, path::Path};
+use std::{
+    fmt,
+    io::{Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use anyhow::Context;
 use clap::Parser;
-use rlox::scanner::scan_tokens;
+use rlox::{
+    expression::{Expr, SideTable},
+    format::format,
+    highlight::{highlight_ansi, highlight_html, STYLESHEET},
+    lint::{Level, LintConfig, Rule},
+    parse,
+    pretty_printing::{print_tree, AstDot, AstPrint, AstRpn},
+    repl::{self, is_incomplete, parse_command},
+    scanner::TokenStream,
+    unparse::unparse_minified,
+    Error,
+};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    /// Path of the script to run, or - to read it from stdin
     script: Option<String>,
+    /// Run the given source instead of a script
+    #[clap(
+        short,
+        long,
+        value_name = "SOURCE",
+        conflicts_with = "script",
+        allow_hyphen_values = true
+    )]
+    eval: Option<String>,
+    /// Print the tokens instead of running the source
+    #[clap(long, conflicts_with_all = &["dump-ast", "minify"])]
+    tokens: bool,
+    /// Print the AST of the parsed expression, as text unless another format
+    /// is given with --dump-ast=FORMAT
+    #[clap(
+        long,
+        alias = "ast",
+        arg_enum,
+        value_name = "FORMAT",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "text"
+    )]
+    dump_ast: Option<AstFormat>,
+    /// Print the parsed expression as the shortest equivalent source
+    #[clap(long, conflicts_with = "dump-ast")]
+    minify: bool,
+    /// Report how long each phase took, on stderr
+    #[clap(long)]
+    time: bool,
+    /// Print what each phase of the pipeline produces, on stderr
+    #[clap(short, long)]
+    verbose: bool,
+    /// Don't print the prompt in the REPL
+    #[clap(short, long, conflicts_with = "verbose")]
+    quiet: bool,
 }
 
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Format source files, printing the result unless --check or --in-place is given
+    Fmt {
+        #[clap(required = true)]
+        files: Vec<PathBuf>,
+        /// Only check that the files are formatted, failing if they are not
+        #[clap(long)]
+        check: bool,
+        /// Rewrite the files in place
+        #[clap(long, short, conflicts_with = "check")]
+        in_place: bool,
+    },
+    /// Check source files for likely mistakes
+    Lint {
+        #[clap(required = true)]
+        files: Vec<PathBuf>,
+        /// Don't check the rule
+        #[clap(long, short = 'A', value_name = "RULE")]
+        allow: Vec<Rule>,
+        /// Report the rule as a warning (the default)
+        #[clap(long, short = 'W', value_name = "RULE")]
+        warn: Vec<Rule>,
+        /// Report the rule as an error, failing the check
+        #[clap(long, short = 'D', value_name = "RULE")]
+        deny: Vec<Rule>,
+    },
+    /// Print a source file with syntax highlighting
+    Highlight {
+        file: PathBuf,
+        #[clap(long, arg_enum, default_value = "ansi")]
+        format: HighlightFormat,
+    },
+    /// Compare the parsed expressions of two files, ignoring layout and comments
+    Diff { old: PathBuf, new: PathBuf },
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum HighlightFormat {
+    /// Colors for the terminal
+    Ansi,
+    /// HTML markup, preceded by its default stylesheet
+    Html,
+}
+
+#[derive(Clone, Copy, clap::ArgEnum)]
+enum AstFormat {
+    /// S-expressions, as echoed by the REPL
+    Text,
+    /// Graphviz DOT graph
+    Dot,
+    /// Reverse Polish notation
+    Rpn,
+    /// Indented tree with source spans
+    Tree,
+}
+
+/// What to print for the source, instead of executing it.
+#[derive(Clone, Copy)]
+enum Output {
+    Tokens,
+    Ast(AstFormat),
+    Minified,
+}
+
+/// How to run source, the same for scripts and the REPL.
+#[derive(Clone, Copy)]
+struct Options {
+    output: Option<Output>,
+    time: bool,
+    verbosity: Verbosity,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Prints pipeline debug output to stderr, if running with --verbose.
+macro_rules! debug {
+    ($options:expr, $($arg:tt)*) => {
+        if $options.verbosity >= Verbosity::Verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Runs a phase of the pipeline, reporting how long it took if `time` is set.
+fn timed<T>(time: bool, phase: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    if time {
+        eprintln!("{phase}: {:?}", start.elapsed());
+    }
+    result
+}
+
+impl Output {
+    fn print(self, expr: &Expr, spans: &SideTable<Range<usize>>) -> Result<String, anyhow::Error> {
+        Ok(match self {
+            Output::Tokens => unreachable!("tokens are printed before parsing"),
+            Output::Ast(AstFormat::Text) => expr.print_ast() + "\n",
+            Output::Ast(AstFormat::Tree) => print_tree(expr, spans),
+            Output::Ast(AstFormat::Dot) => expr.print_dot(),
+            Output::Ast(AstFormat::Rpn) => expr.print_ast_rpn() + "\n",
+            Output::Minified => unparse_minified(expr)? + "\n",
+        })
+    }
+}
+
+/// The source has errors, which have already been reported.
+#[derive(Debug)]
+struct CompileError {
+    errors: usize,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} error(s) in the source", self.errors)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Exit code for source that fails to compile, `EX_DATAERR` from sysexits.h.
+const EXIT_COMPILE_ERROR: i32 = 65;
+
 fn main() -> Result<(), anyhow::Error> {
-    let cli = Cli::parse();
+    match run_cli(Cli::parse()) {
+        Err(error) if error.is::<CompileError>() => std::process::exit(EXIT_COMPILE_ERROR),
+        result => result,
+    }
+}
 
-    match cli.script {
-        Some(filepath) => {
-            println!("Running script {filepath}");
-            run_file(Path::new(&filepath))?;
+fn run_cli(cli: Cli) -> Result<(), anyhow::Error> {
+    match cli.command {
+        Some(Command::Fmt {
+            files,
+            check,
+            in_place,
+        }) => return fmt(&files, check, in_place),
+        Some(Command::Lint {
+            files,
+            allow,
+            warn,
+            deny,
+        }) => {
+            let levels = [
+                (allow, Level::Allow),
+                (warn, Level::Warn),
+                (deny, Level::Deny),
+            ];
+            let config = levels
+                .into_iter()
+                .flat_map(|(rules, level)| rules.into_iter().map(move |rule| (rule, level)))
+                .fold(LintConfig::default(), |config, (rule, level)| {
+                    config.with_level(rule, level)
+                });
+            return lint(&files, &config);
+        }
+        Some(Command::Highlight { file, format }) => {
+            let source = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            match format {
+                HighlightFormat::Ansi => print!("{}", highlight_ansi(&source)),
+                HighlightFormat::Html => {
+                    println!("<style>\n{STYLESHEET}</style>");
+                    print!("{}", highlight_html(&source));
+                }
+            }
+            return Ok(());
         }
-        None => run_prompt()?,
+        Some(Command::Diff { old, new }) => return diff(&old, &new),
+        None => {}
     }
 
-    Ok(())
+    let output = match (cli.tokens, cli.dump_ast, cli.minify) {
+        (true, _, _) => Some(Output::Tokens),
+        (false, Some(format), _) => Some(Output::Ast(format)),
+        (false, None, true) => Some(Output::Minified),
+        (false, None, false) => None,
+    };
+    let options = Options {
+        output,
+        time: cli.time,
+        verbosity: match (cli.quiet, cli.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, true) => Verbosity::Verbose,
+            (false, false) => Verbosity::Normal,
+        },
+    };
+    match (cli.script, cli.eval) {
+        (_, Some(source)) => run(&source, options),
+        (Some(filepath), None) if filepath == "-" => {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .context("Failed to read the script from stdin")?;
+            run(&source, options)
+        }
+        (Some(filepath), None) => {
+            debug!(options, "Running script {filepath}");
+            run_file(Path::new(&filepath), options)
+        }
+        (None, None) => run_prompt(options),
+    }
 }
 
-fn run_file(path: &Path) -> Result<(), anyhow::Error> {
+fn run_file(path: &Path, options: Options) -> Result<(), anyhow::Error> {
     let source = std::fs::read_to_string(path).context("Failed to read source file")?;
-    run(source)?;
-
-    Ok(())
+    run(&source, options)
 }
 
-fn run_prompt() -> Result<(), anyhow::Error> {
+fn run_prompt(options: Options) -> Result<(), anyhow::Error> {
     let mut buffer = String::new();
+    // The source entered so far, for :save
+    let mut history = String::new();
     loop {
-        print!("> ");
-        std::io::stdout().flush().unwrap();
+        if options.verbosity > Verbosity::Quiet {
+            // Continuation lines of unfinished input get their own prompt
+            print!("{}", if buffer.is_empty() { "> " } else { ".. " });
+            std::io::stdout().flush().unwrap();
+        }
         let stdin = std::io::stdin();
-        stdin.read_line(&mut buffer)?;
-
-        let tokens = scan_tokens(&buffer)?;
-        println!("Executing: '{:?}'", tokens.collect::<Vec<_>>());
+        if stdin.read_line(&mut buffer)? == 0 {
+            // End of input (Ctrl-D)
+            return Ok(());
+        }
+        let result = match parse_command(&buffer) {
+            Some(command) => run_command(command, &mut history, options),
+            None if is_incomplete(&buffer) => continue,
+            None => {
+                history.push_str(&buffer);
+                run(&buffer, options)
+            }
+        };
         buffer.clear();
+        // Errors in the source have already been reported, the session goes on
+        if let Err(error) = result {
+            if !error.is::<CompileError>() {
+                eprintln!("{error:#}");
+            }
+        }
     }
 }
 
-fn run(source: String) -> Result<(), anyhow::Error> {
-    for token in scan_tokens(&source)? {
-        println!("New token: {:?}", token);
+fn run_command(
+    command: Result<repl::Command, String>,
+    history: &mut String,
+    options: Options,
+) -> Result<(), anyhow::Error> {
+    match command.map_err(anyhow::Error::msg)? {
+        repl::Command::Load(path) => {
+            let source =
+                std::fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?;
+            run(&source, options)?;
+            history.push_str(&source);
+            if !source.ends_with('\n') {
+                history.push('\n');
+            }
+        }
+        repl::Command::Save(path) => {
+            std::fs::write(path, &*history).with_context(|| format!("Failed to write {path}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs source through the whole pipeline, the same for scripts and the REPL.
+/// Every error in the source is reported to stderr before failing with `CompileError`.
+fn run(source: &str, options: Options) -> Result<(), anyhow::Error> {
+    let scanned = timed(options.time, "scan", || rlox::scan(source));
+    debug!(options, "Scanned: {} tokens", scanned.tokens.len());
+    if let Some(Output::Tokens) = options.output {
+        for token in &scanned.tokens {
+            let span = format!("{:?}", token.span);
+            let ty = format!("{:?}", token.ty);
+            println!("{:>4} {span:<10} {ty:<16} {:?}", token.line, token.lexeme);
+        }
+        return report(None, source, &scanned.errors);
+    }
+
+    let program = match timed(options.time, "parse", || scanned.parse()) {
+        Ok(program) => program,
+        Err(errors) => return report(None, source, &errors),
+    };
+    match options.output {
+        Some(output) => print!("{}", output.print(&program.expr, &program.spans)?),
+        None => debug!(options, "Executing: '{}'", program.expr.print_ast()),
+    }
+
+    Ok(())
+}
+
+/// Prints the errors found in the source to stderr, prefixed with the path
+/// of the file it was read from, if any.
+fn report(path: Option<&Path>, source: &str, errors: &[Error]) -> Result<(), anyhow::Error> {
+    for error in errors {
+        match path {
+            Some(path) => eprintln!("{}: {}", path.display(), error.render(source)),
+            None => eprintln!("{}", error.render(source)),
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CompileError {
+            errors: errors.len(),
+        }
+        .into())
+    }
+}
+
+/// Compiles the source of a file for the subcommands, reporting its errors
+/// the same way `run` does.
+fn compile_file(path: &Path, source: &str) -> Result<rlox::Program, anyhow::Error> {
+    rlox::compile(source).map_err(|errors| {
+        report(Some(path), source, &errors).expect_err("compiling fails with at least one error")
+    })
+}
+
+fn fmt(files: &[PathBuf], check: bool, in_place: bool) -> Result<(), anyhow::Error> {
+    let mut unformatted = 0;
+    for path in files {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        compile_file(path, &source)?;
+        let tokens = TokenStream::new(&source)
+            .with_trivia()
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Failed to scan {}", path.display()))?;
+        let tree = parse::Parser::new(tokens)
+            .parse_syntax_tree()
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let formatted = format(&tree);
+
+        if check {
+            if formatted != source {
+                eprintln!("{} is not formatted", path.display());
+                unformatted += 1;
+            }
+        } else if in_place {
+            if formatted != source {
+                std::fs::write(path, formatted)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+        } else {
+            print!("{formatted}");
+        }
+    }
+    if unformatted > 0 {
+        anyhow::bail!("{unformatted} file(s) need formatting");
+    }
+
+    Ok(())
+}
+
+fn lint(files: &[PathBuf], config: &LintConfig) -> Result<(), anyhow::Error> {
+    let mut errors = 0;
+    for path in files {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let program = compile_file(path, &source)?;
+        for diagnostic in rlox::lint::lint(&program.expr, &program.spans, config) {
+            let line = source[..diagnostic.span.start].matches('\n').count() + 1;
+            eprintln!(
+                "{}:{line}: {}[{}]: {}",
+                path.display(),
+                diagnostic.level,
+                diagnostic.rule,
+                diagnostic.message
+            );
+            if diagnostic.level == Level::Deny {
+                errors += 1;
+            }
+        }
+    }
+    if errors > 0 {
+        anyhow::bail!("{errors} lint error(s) found");
+    }
+
+    Ok(())
+}
+
+fn diff(old_path: &Path, new_path: &Path) -> Result<(), anyhow::Error> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+    let (old_source, new_source) = (read(old_path)?, read(new_path)?);
+    let old = compile_file(old_path, &old_source)?;
+    let new = compile_file(new_path, &new_source)?;
+
+    let changes = rlox::diff::diff(&old.expr, &new.expr);
+    for change in &changes {
+        for (sign, path, source, program, expr) in [
+            ('-', old_path, &old_source, &old, change.old),
+            ('+', new_path, &new_source, &new, change.new),
+        ] {
+            let span = program.spans.get(expr.id()).cloned().unwrap_or_default();
+            let line = source[..span.start].matches('\n').count() + 1;
+            println!("{}:{line}: {sign} {}", path.display(), &source[span]);
+        }
+    }
+    if !changes.is_empty() {
+        anyhow::bail!("{} change(s) found", changes.len());
     }
 
     Ok(())
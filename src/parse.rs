@@ -0,0 +1,855 @@
+//! A Pratt parser: infix operators are looked up in a table by token type
+//! and bind by precedence, loosest first:
+//!
+//! equality    → "!=" | "==" ;
+//! comparison  → ">" | ">=" | "<" | "<=" ;
+//! term        → "-" | "+" ;
+//! factor      → "/" | "*" ;
+//! unary       → ( "!" | "-" ) unary | primary ;
+//! primary     → NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")" ;
+
+use std::{fmt, ops::Range};
+
+use itertools::Itertools;
+
+use crate::{
+    cst::{Builder, Checkpoint, SyntaxKind, SyntaxNode},
+    expression::{
+        BinaryExpr, Expr, GroupingExpr, Literal, LiteralExpr, NodeId, Operator, SideTable, Unary,
+        UnaryExpr,
+    },
+    token::{Token, TokenType},
+};
+
+/// How tightly an operator binds, loosest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Precedence {
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+}
+
+impl Precedence {
+    pub(crate) fn of(operator: Operator) -> Self {
+        match operator {
+            Operator::Equal | Operator::NotEqual => Precedence::Equality,
+            Operator::Greater | Operator::GreaterEqual | Operator::Less | Operator::LessEqual => {
+                Precedence::Comparison
+            }
+            Operator::Minus | Operator::Plus => Precedence::Term,
+            Operator::Divide | Operator::Multiply => Precedence::Factor,
+        }
+    }
+
+    pub(crate) fn next(self) -> Self {
+        match self {
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor | Precedence::Unary => Precedence::Unary,
+        }
+    }
+}
+
+/// The table of infix operators. New binary operators only need an entry here
+/// and their precedence in `Precedence::of`.
+fn infix_operator(ty: &TokenType) -> Option<(Precedence, Operator)> {
+    let operator = match ty {
+        TokenType::BangEqual => Operator::NotEqual,
+        TokenType::EqualEqual => Operator::Equal,
+        TokenType::Greater => Operator::Greater,
+        TokenType::GreaterEqual => Operator::GreaterEqual,
+        TokenType::Less => Operator::Less,
+        TokenType::LessEqual => Operator::LessEqual,
+        TokenType::Minus => Operator::Minus,
+        TokenType::Plus => Operator::Plus,
+        TokenType::Slash => Operator::Divide,
+        TokenType::Star => Operator::Multiply,
+        _ => return None,
+    };
+    Some((Precedence::of(operator), operator))
+}
+
+/// Something the parser would have accepted where it failed.
+#[derive(Debug, Clone, PartialEq, parse_display::Display)]
+pub enum Expected {
+    #[display("expression")]
+    Expression,
+    #[display("operator")]
+    Operator,
+    #[display("'{0}'")]
+    Token(TokenType),
+    #[display("end of input")]
+    EndOfInput,
+}
+
+/// An opening delimiter that is missing its closing counterpart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unclosed {
+    pub delimiter: TokenType,
+    pub span: Range<usize>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    Unexpected {
+        expected: Vec<Expected>,
+        /// What the expected tokens would have followed, e.g. "after expression".
+        context: Option<&'static str>,
+        /// The opening delimiter, when the closing one was expected.
+        unclosed: Option<Box<Unclosed>>,
+    },
+    TooDeeplyNested,
+    /// The input has more tokens than the parser's budget allows.
+    TooManyTokens {
+        limit: usize,
+    },
+    /// Parsing would produce more AST nodes than the parser's budget allows.
+    TooManyNodes {
+        limit: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Lexeme of the token where parsing failed, empty at the end of input.
+    pub found: String,
+    pub span: Range<usize>,
+    /// 0-based, displayed 1-based like editors do.
+    pub line: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let found = if self.span.is_empty() {
+            "end of input".to_string()
+        } else {
+            format!("'{}'", self.found)
+        };
+        write!(f, "[line {}] Error: ", self.line + 1)?;
+        match &self.kind {
+            ParseErrorKind::Unexpected {
+                expected,
+                context,
+                unclosed,
+            } => {
+                write!(f, "expected {}", expected.iter().join(" or "))?;
+                if let Some(context) = context {
+                    write!(f, " {context}")?;
+                }
+                write!(f, ", found {found}")?;
+                if let Some(unclosed) = unclosed {
+                    write!(
+                        f,
+                        "; unclosed '{}' opened at line {}",
+                        unclosed.delimiter,
+                        unclosed.line + 1
+                    )?;
+                }
+                Ok(())
+            }
+            ParseErrorKind::TooDeeplyNested => {
+                write!(f, "too deeply nested expression at {found}")
+            }
+            ParseErrorKind::TooManyTokens { limit } => {
+                write!(f, "input has more than {limit} tokens")
+            }
+            ParseErrorKind::TooManyNodes { limit } => {
+                write!(f, "expression has more than {limit} nodes at {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+pub struct Parser<'a> {
+    /// Tokens not pulled into `tokens` yet.
+    input: Box<dyn Iterator<Item = Token<'a>> + 'a>,
+    /// The tokens pulled from the input so far, the last one is pulled when needed.
+    tokens: Vec<Token<'a>>,
+    /// Set when the input was cut off at the token budget.
+    truncated: bool,
+    current: usize,
+    depth: usize,
+    max_depth: usize,
+    /// What was tried at the current token, for error reporting.
+    expected: Vec<Expected>,
+    /// Only set while building a concrete syntax tree.
+    builder: Option<Builder<'a>>,
+    recover: bool,
+    errors: Vec<ParseError>,
+    /// Set after an error until parsing gets back on track, to avoid cascading errors.
+    panicking: bool,
+    next_id: usize,
+    max_tokens: usize,
+    max_nodes: usize,
+    spans: SideTable<Range<usize>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Tokens are pulled from the iterator as parsing goes, so a token budget
+    /// also bounds how many of them are held in memory.
+    pub fn new(tokens: impl IntoIterator<Item = Token<'a>> + 'a) -> Self {
+        Self {
+            input: Box::new(tokens.into_iter()),
+            tokens: Vec::new(),
+            truncated: false,
+            current: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            expected: Vec::new(),
+            builder: None,
+            recover: false,
+            errors: Vec::new(),
+            panicking: false,
+            next_id: 0,
+            max_tokens: usize::MAX,
+            max_nodes: usize::MAX,
+            spans: SideTable::default(),
+        }
+    }
+
+    /// Recover from parse errors instead of stopping at the first one: the parts that
+    /// fail to parse become `Expr::Error` nodes and parsing continues, so tools can
+    /// still work with the rest of the input. The errors are available from `errors`.
+    pub fn with_recovery(mut self) -> Self {
+        self.recover = true;
+        self
+    }
+
+    /// Errors reported while parsing with recovery.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Source spans of the parsed nodes, keyed by their ids.
+    pub fn spans(&self) -> &SideTable<Range<usize>> {
+        &self.spans
+    }
+
+    /// Limit how deeply expressions may nest, so that pathological input
+    /// is rejected instead of overflowing the stack.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limit how many tokens and AST nodes a single parse may take, so that
+    /// oversized input is rejected instead of using unbounded memory.
+    /// Exceeding the budget always fails the parse, even when recovering from errors.
+    pub fn with_budget(mut self, max_tokens: usize, max_nodes: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    /// Parses all tokens as a single expression, as typed in the REPL.
+    pub fn parse_expression(&mut self) -> Result<Expr, ParseError> {
+        if self.tokens.is_empty() {
+            self.load();
+        }
+        let result = self.expression_to_end();
+        // Whatever was parsed of input cut off at the budget is incomplete
+        if self.truncated {
+            while self.peek().ty != TokenType::Eof {
+                self.advance();
+            }
+            let limit = self.max_tokens;
+            return Err(self.error(ParseErrorKind::TooManyTokens { limit }));
+        }
+        result
+    }
+
+    fn expression_to_end(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.expression()?;
+        if !self.check(Expected::EndOfInput) {
+            let error = self.unexpected(None);
+            self.report(error)?;
+            // Skip whatever follows the expression
+            self.start_node(SyntaxKind::Error);
+            while self.peek().ty != TokenType::Eof {
+                self.advance();
+            }
+            self.finish_node();
+        }
+        Ok(expr)
+    }
+
+    /// Parses all tokens as a single expression into a lossless syntax tree.
+    /// Scan the tokens with trivia for the tree to include whitespace and comments.
+    pub fn parse_syntax_tree(&mut self) -> Result<SyntaxNode<'a>, ParseError> {
+        let mut builder = Builder::default();
+        builder.start_node(SyntaxKind::Root);
+        self.builder = Some(builder);
+        let result = self.parse_expression();
+
+        let mut builder = self.builder.take().expect("builder is set while parsing");
+        result?;
+        builder.token(self.peek().clone());
+        builder.finish_node();
+        Ok(builder.finish())
+    }
+
+    fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.expression_with_precedence(Precedence::Equality)
+    }
+
+    /// Parses an expression whose infix operators all bind at least as tightly as `min`.
+    fn expression_with_precedence(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        if self.depth >= self.max_depth {
+            self.report(self.error(ParseErrorKind::TooDeeplyNested))?;
+            return Ok(Expr::Error(self.node_id(self.current)?));
+        }
+        self.depth += 1;
+        let start = self.current;
+        let checkpoint = self.builder.as_ref().map(Builder::checkpoint);
+        let expr = self
+            .prefix()
+            .and_then(|expr| self.infix(expr, min, start, checkpoint));
+        self.depth -= 1;
+        expr
+    }
+
+    fn infix(
+        &mut self,
+        mut expr: Expr,
+        min: Precedence,
+        start: usize,
+        checkpoint: Option<Checkpoint>,
+    ) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        while let Some((precedence, operator)) = self.infix_operator() {
+            if precedence < min {
+                break;
+            }
+            // Every operator nests the expression so far one level deeper
+            if self.depth >= self.max_depth {
+                self.report(self.error(ParseErrorKind::TooDeeplyNested))?;
+                break;
+            }
+            if let (Some(builder), Some(checkpoint)) = (&mut self.builder, checkpoint) {
+                builder.start_node_at(checkpoint, SyntaxKind::Binary);
+            }
+            self.advance();
+            // Operators are left-associative, so the right operand binds tighter
+            let right = self.expression_with_precedence(precedence.next())?;
+            self.finish_node();
+            expr = Expr::Binary(BinaryExpr {
+                id: self.node_id(start)?,
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+            self.depth += 1;
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn prefix(&mut self) -> Result<Expr, ParseError> {
+        let unary = match self.peek().ty {
+            TokenType::Bang => Unary::Bang,
+            TokenType::Minus => Unary::Minus,
+            _ => return self.primary(),
+        };
+        let start = self.current;
+        self.start_node(SyntaxKind::Unary);
+        self.advance();
+        let expr = self.expression_with_precedence(Precedence::Unary)?;
+        self.finish_node();
+        Ok(Expr::Unary(UnaryExpr {
+            id: self.node_id(start)?,
+            unary,
+            expr: Box::new(expr),
+        }))
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current;
+        let literal = match &self.peek().ty {
+            TokenType::Number(v) => Literal::Number(*v),
+            TokenType::String => {
+                let token = self.peek();
+                let contents = token.string_contents().unwrap_or(token.lexeme);
+                Literal::String(contents.to_string())
+            }
+            TokenType::True => Literal::Bool(true),
+            TokenType::False => Literal::Bool(false),
+            TokenType::Nil => Literal::Nil,
+            TokenType::LeftParen => {
+                self.start_node(SyntaxKind::Grouping);
+                self.advance();
+                let expr = self.expression()?;
+                if self.check(Expected::Token(TokenType::RightParen)) {
+                    self.advance();
+                    self.panicking = false;
+                } else {
+                    let opener = &self.tokens[start];
+                    let unclosed = Unclosed {
+                        delimiter: opener.ty.clone(),
+                        span: opener.span.clone(),
+                        line: opener.line,
+                    };
+                    let expected = std::mem::take(&mut self.expected);
+                    let error = self.error(ParseErrorKind::Unexpected {
+                        expected,
+                        context: Some("after expression"),
+                        unclosed: Some(Box::new(unclosed)),
+                    });
+                    self.report(error)?;
+                }
+                self.finish_node();
+                return Ok(Expr::Grouping(GroupingExpr {
+                    id: self.node_id(start)?,
+                    expr: Box::new(expr),
+                }));
+            }
+            _ => {
+                self.expected.push(Expected::Expression);
+                let error = self.unexpected(None);
+                self.report(error)?;
+                // Skip the offending token, unless it can continue the enclosing expression
+                self.start_node(SyntaxKind::Error);
+                let ty = &self.peek().ty;
+                if !matches!(ty, TokenType::Eof | TokenType::RightParen)
+                    && infix_operator(ty).is_none()
+                {
+                    self.advance();
+                }
+                self.finish_node();
+                return Ok(Expr::Error(self.node_id(start)?));
+            }
+        };
+        self.start_node(SyntaxKind::Literal);
+        self.advance();
+        self.finish_node();
+        self.panicking = false;
+        Ok(Expr::Literal(LiteralExpr {
+            id: self.node_id(start)?,
+            value: literal,
+        }))
+    }
+
+    /// Allocates the id of a node spanning from the token at `start` to the last consumed token.
+    fn node_id(&mut self, start: usize) -> Result<NodeId, ParseError> {
+        if self.next_id >= self.max_nodes {
+            let limit = self.max_nodes;
+            return Err(self.error(ParseErrorKind::TooManyNodes { limit }));
+        }
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        let start = self.tokens[start].span.start;
+        let end = match self.current.checked_sub(1) {
+            Some(last) => self.tokens[last].span.end.max(start),
+            None => start,
+        };
+        self.spans.insert(id, start..end);
+        Ok(id)
+    }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        if let Some(builder) = &mut self.builder {
+            builder.start_node(kind);
+        }
+    }
+
+    fn finish_node(&mut self) {
+        if let Some(builder) = &mut self.builder {
+            builder.finish_node();
+        }
+    }
+
+    fn infix_operator(&mut self) -> Option<(Precedence, Operator)> {
+        let operator = infix_operator(&self.peek().ty);
+        if operator.is_none() && !self.expected.contains(&Expected::Operator) {
+            self.expected.push(Expected::Operator);
+        }
+        operator
+    }
+
+    /// Whether the current token is the `expected` one. If not, it is remembered for error reporting.
+    fn check(&mut self, expected: Expected) -> bool {
+        let matches = match &expected {
+            Expected::Token(ty) => self.peek().ty == *ty,
+            Expected::EndOfInput => self.peek().ty == TokenType::Eof,
+            Expected::Expression | Expected::Operator => false,
+        };
+        if !matches {
+            self.expected.push(expected);
+        }
+        matches
+    }
+
+    /// Fails with the error, or records it when recovering from errors.
+    fn report(&mut self, error: ParseError) -> Result<(), ParseError> {
+        if !self.recover {
+            return Err(error);
+        }
+        if !self.panicking {
+            self.errors.push(error);
+            self.panicking = true;
+        }
+        Ok(())
+    }
+
+    fn unexpected(&mut self, context: Option<&'static str>) -> ParseError {
+        let expected = std::mem::take(&mut self.expected);
+        self.error(ParseErrorKind::Unexpected {
+            expected,
+            context,
+            unclosed: None,
+        })
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        let token = self.peek();
+        ParseError {
+            kind,
+            found: token.lexeme.to_string(),
+            span: token.span.clone(),
+            line: token.line,
+        }
+    }
+
+    fn peek(&self) -> &Token<'a> {
+        &self.tokens[self.current]
+    }
+
+    /// Pulls the next token from the input. The input ends with `Eof` when it runs
+    /// out, or in place of the first token past the budget.
+    fn load(&mut self) {
+        let eof = |span: Range<usize>, line| Token {
+            ty: TokenType::Eof,
+            lexeme: "",
+            span,
+            line,
+            trivia: Vec::new(),
+        };
+        let token = match self.input.next() {
+            Some(token) if token.ty != TokenType::Eof && self.tokens.len() >= self.max_tokens => {
+                self.truncated = true;
+                eof(token.span.start..token.span.start, token.line)
+            }
+            Some(token) => token,
+            None => {
+                let end = self.tokens.last().map_or(0, |t| t.span.end);
+                eof(end..end, self.tokens.last().map_or(0, |t| t.line))
+            }
+        };
+        self.tokens.push(token);
+    }
+
+    /// Consumes the current token. The trailing `Eof` is never consumed.
+    fn advance(&mut self) -> &Token<'a> {
+        let current = self.current;
+        if self.tokens[current].ty != TokenType::Eof {
+            self.current += 1;
+            if self.current == self.tokens.len() {
+                self.load();
+            }
+            if let Some(builder) = &mut self.builder {
+                builder.token(self.tokens[current].clone());
+            }
+        }
+        self.expected.clear();
+        &self.tokens[current]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        pretty_printing::AstPrint,
+        scanner::{scan_tokens, TokenStream},
+    };
+
+    use crate::expression::{Expr, NodeId};
+
+    use crate::token::TokenType;
+
+    use super::{Expected, ParseError, ParseErrorKind, Parser, Unclosed, DEFAULT_MAX_DEPTH};
+
+    fn parse(source: &str) -> Result<String, anyhow::Error> {
+        let expr = Parser::new(scan_tokens(source)?).parse_expression()?;
+        Ok(expr.print_ast())
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(parse("1").unwrap(), "1");
+        assert_eq!(parse("\"foo\"").unwrap(), "\"foo\"");
+        assert_eq!(parse("true").unwrap(), "true");
+        assert_eq!(parse("false").unwrap(), "false");
+        assert_eq!(parse("nil").unwrap(), "nil");
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(parse("1 + 2 * 3").unwrap(), "(+ 1 (* 2 3))");
+        assert_eq!(parse("(1 + 2) * 3").unwrap(), "(* (group (+ 1 2)) 3)");
+        assert_eq!(
+            parse("1 < 2 == 3 >= 4 - -5 / !6").unwrap(),
+            "(== (< 1 2) (>= 3 (- 4 (/ (- 5) (! 6)))))"
+        );
+        assert_eq!(
+            parse("-123 * (45.67)").unwrap(),
+            "(* (- 123) (group 45.67))"
+        );
+    }
+
+    #[test]
+    fn left_associative() {
+        assert_eq!(parse("1 - 2 - 3").unwrap(), "(- (- 1 2) 3)");
+        assert_eq!(parse("1 == 2 != 3").unwrap(), "(!= (== 1 2) 3)");
+        assert_eq!(parse("!!true").unwrap(), "(! (! true))");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse("(1 + 2").unwrap_err().to_string(),
+            "[line 1] Error: expected operator or ')' after expression, found end of input; unclosed '(' opened at line 1"
+        );
+        assert_eq!(
+            parse("(1 + 2;").unwrap_err().to_string(),
+            "[line 1] Error: expected operator or ')' after expression, found ';'; unclosed '(' opened at line 1"
+        );
+        assert_eq!(
+            parse("1 +").unwrap_err().to_string(),
+            "[line 1] Error: expected expression, found end of input"
+        );
+        assert_eq!(
+            parse("1 2").unwrap_err().to_string(),
+            "[line 1] Error: expected operator or end of input, found '2'"
+        );
+        assert_eq!(
+            parse(")").unwrap_err().to_string(),
+            "[line 1] Error: expected expression, found ')'"
+        );
+        assert_eq!(
+            Parser::new(scan_tokens("\n-(1 +\n)").unwrap())
+                .parse_expression()
+                .unwrap_err(),
+            ParseError {
+                kind: ParseErrorKind::Unexpected {
+                    expected: vec![Expected::Expression],
+                    context: None,
+                    unclosed: None,
+                },
+                found: ")".to_string(),
+                span: 7..8,
+                line: 2
+            }
+        );
+    }
+
+    #[test]
+    fn unclosed_delimiter() {
+        let error = Parser::new(scan_tokens("1 +\n(2 *\n  (3 - 4)\n").unwrap())
+            .parse_expression()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[line 3] Error: expected operator or ')' after expression, found end of input; \
+             unclosed '(' opened at line 2"
+        );
+        let ParseErrorKind::Unexpected { unclosed, .. } = error.kind else {
+            panic!("unexpected error kind: {:?}", error.kind);
+        };
+        assert_eq!(
+            unclosed,
+            Some(Box::new(Unclosed {
+                delimiter: TokenType::LeftParen,
+                span: 4..5,
+                line: 1,
+            }))
+        );
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = |depth| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        let parse_nested = |depth, max_depth| {
+            let source = nested(depth);
+            let result = Parser::new(scan_tokens(&source).unwrap())
+                .with_max_depth(max_depth)
+                .parse_expression();
+            result
+        };
+
+        assert!(parse_nested(2, 3).is_ok());
+        assert_eq!(
+            parse_nested(3, 3).unwrap_err().to_string(),
+            "[line 1] Error: too deeply nested expression at '1'"
+        );
+        assert!(parse("---1").is_ok());
+        assert!(Parser::new(scan_tokens("---1").unwrap())
+            .with_max_depth(3)
+            .parse_expression()
+            .is_err());
+
+        assert!(parse_nested(DEFAULT_MAX_DEPTH - 1, DEFAULT_MAX_DEPTH).is_ok());
+        assert!(parse_nested(100_000, DEFAULT_MAX_DEPTH).is_err());
+        assert!(parse(&"-".repeat(100_000)).is_err());
+
+        // Operator chains nest to the left without recursing
+        let chain = |terms| vec!["1"; terms].join(" + ");
+        assert!(parse(&chain(DEFAULT_MAX_DEPTH - 1)).is_ok());
+        assert_eq!(
+            parse(&chain(100_000)).unwrap_err().to_string(),
+            "[line 1] Error: too deeply nested expression at '+'"
+        );
+        assert_eq!(
+            parse_recovering(&chain(100_000)).1,
+            vec!["[line 1] Error: too deeply nested expression at '+'"]
+        );
+    }
+
+    fn parse_recovering(source: &str) -> (String, Vec<String>) {
+        let tokens = TokenStream::new(source)
+            .with_error_tokens()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut parser = Parser::new(tokens).with_recovery();
+        let expr = parser.parse_expression().unwrap();
+        let errors = parser.errors().iter().map(|e| e.to_string()).collect();
+        (expr.print_ast(), errors)
+    }
+
+    #[test]
+    fn recovery() {
+        assert_eq!(parse_recovering("1 + 2"), ("(+ 1 2)".to_string(), vec![]));
+        assert_eq!(
+            parse_recovering("1 + * 2"),
+            (
+                "(+ 1 (* <error> 2))".to_string(),
+                vec!["[line 1] Error: expected expression, found '*'".to_string()]
+            )
+        );
+        assert_eq!(
+            parse_recovering("(1 + @) == -(2"),
+            (
+                "(== (group (+ 1 <error>)) (- (group 2)))".to_string(),
+                vec![
+                    "[line 1] Error: expected expression, found '@'".to_string(),
+                    "[line 1] Error: expected operator or ')' after expression, found end of input; unclosed '(' opened at line 1"
+                        .to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            parse_recovering("((((1"),
+            (
+                "(group (group (group (group 1))))".to_string(),
+                vec![
+                    "[line 1] Error: expected operator or ')' after expression, found end of input; unclosed '(' opened at line 1"
+                        .to_string()
+                ]
+            )
+        );
+        assert_eq!(
+            parse_recovering("1 2 3"),
+            (
+                "1".to_string(),
+                vec!["[line 1] Error: expected operator or end of input, found '2'".to_string()]
+            )
+        );
+
+        let (_, errors) = parse_recovering(&"(".repeat(100_000));
+        assert_eq!(
+            errors,
+            vec!["[line 1] Error: too deeply nested expression at '('".to_string()]
+        );
+    }
+
+    #[test]
+    fn recovery_syntax_tree() {
+        let source = "(1 + @)  2 // trailing";
+        let tokens = TokenStream::new(source)
+            .with_trivia()
+            .with_error_tokens()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut parser = Parser::new(tokens).with_recovery();
+        let tree = parser.parse_syntax_tree().unwrap();
+        assert_eq!(tree.text(), source);
+        assert_eq!(parser.errors().len(), 2);
+    }
+
+    #[test]
+    fn node_ids() {
+        let source = "-1 + (2)";
+        let mut parser = Parser::new(scan_tokens(source).unwrap());
+        let expr = parser.parse_expression().unwrap();
+        assert_eq!(expr.id(), NodeId(4));
+        let Expr::Binary(binary) = &expr else {
+            panic!("expected a binary expression, got {expr:?}");
+        };
+        assert_eq!(binary.left.id(), NodeId(1));
+        assert_eq!(binary.right.id(), NodeId(3));
+
+        let span = |id| &source[parser.spans().get(NodeId(id)).unwrap().clone()];
+        assert_eq!(span(0), "1");
+        assert_eq!(span(1), "-1");
+        assert_eq!(span(2), "2");
+        assert_eq!(span(3), "(2)");
+        assert_eq!(span(4), source);
+        assert_eq!(parser.spans().get(NodeId(5)), None);
+
+        // Ids are stable: parsing the same source again assigns the same ones
+        let again = Parser::new(scan_tokens(source).unwrap())
+            .parse_expression()
+            .unwrap();
+        assert_eq!(again, expr);
+    }
+
+    #[test]
+    fn budget() {
+        let parse_with_budget = |source, max_tokens, max_nodes| {
+            Parser::new(scan_tokens(source).unwrap())
+                .with_budget(max_tokens, max_nodes)
+                .with_recovery()
+                .parse_expression()
+                .map(|expr| expr.print_ast())
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(parse_with_budget("1 + 2", 3, 3), Ok("(+ 1 2)".to_string()));
+        assert_eq!(
+            parse_with_budget("1 + 2", 2, 3),
+            Err("[line 1] Error: input has more than 2 tokens".to_string())
+        );
+        assert_eq!(
+            parse_with_budget("1 + 2 * 3", 5, 4),
+            Err("[line 1] Error: expression has more than 4 nodes at end of input".to_string())
+        );
+        assert_eq!(
+            parse_with_budget("1 + ; * 2", 5, 3),
+            Err("[line 1] Error: expression has more than 3 nodes at end of input".to_string())
+        );
+
+        // Tokens past the budget are never pulled from the input
+        let one = scan_tokens("1").unwrap().next().unwrap();
+        let error = Parser::new(std::iter::repeat(one))
+            .with_budget(10, usize::MAX)
+            .with_recovery()
+            .parse_expression()
+            .unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::TooManyTokens { limit: 10 });
+    }
+
+    #[test]
+    fn missing_eof() {
+        let tokens = scan_tokens("1 + 2")
+            .unwrap()
+            .filter(|t| t.ty != crate::token::TokenType::Eof);
+        assert!(Parser::new(tokens).parse_expression().is_ok());
+    }
+}
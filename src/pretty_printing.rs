@@ -1,6 +1,8 @@
-use std::fmt::Write;
+use std::{fmt::Write, ops::Range};
 
-use crate::expression::{BinaryExpr, Expr, GroupingExpr, Literal, UnaryExpr};
+use crate::expression::{
+    BinaryExpr, Expr, GroupingExpr, Literal, LiteralExpr, SideTable, Unary, UnaryExpr,
+};
 
 pub trait AstPrint {
     fn write_to(&self, f: &mut impl Write) -> std::fmt::Result;
@@ -18,11 +20,28 @@ impl AstPrint for Literal {
             Literal::Nil => f.write_str("nil"),
             Literal::Bool(v) => write!(f, "{}", v),
             Literal::Number(v) => write!(f, "{}", v),
-            Literal::String(v) => write!(f, "{}", v),
+            Literal::String(v) => {
+                // Quoted so that strings never read back as another atom
+                f.write_char('"')?;
+                for c in v.chars() {
+                    match c {
+                        '"' | '\\' => write!(f, "\\{c}")?,
+                        '\n' => f.write_str("\\n")?,
+                        c => f.write_char(c)?,
+                    }
+                }
+                f.write_char('"')
+            }
         }
     }
 }
 
+impl AstPrint for LiteralExpr {
+    fn write_to(&self, f: &mut impl Write) -> std::fmt::Result {
+        self.value.write_to(f)
+    }
+}
+
 impl AstPrint for GroupingExpr {
     fn write_to(&self, f: &mut impl Write) -> std::fmt::Result {
         f.write_str("(group ")?;
@@ -59,40 +78,321 @@ impl AstPrint for Expr {
             Expr::Unary(v) => v.write_to(f),
             Expr::Binary(v) => v.write_to(f),
             Expr::Literal(v) => v.write_to(f),
+            Expr::Error(_) => f.write_str("<error>"),
+        }
+    }
+}
+
+/// Prints an AST as an indented tree, one node per line with its kind and,
+/// if `spans` has it, its source span:
+///
+/// ```text
+/// Binary * [0..14]
+/// ├── Unary - [0..4]
+/// │   └── Literal 123 [1..4]
+/// └── Grouping [7..14]
+///     └── Literal 45.67 [8..13]
+/// ```
+pub fn print_tree(expr: &Expr, spans: &SideTable<Range<usize>>) -> String {
+    let mut output = String::new();
+    write_tree(expr, spans, "", "", &mut output).expect("printing a tree returned an error");
+    output
+}
+
+/// Writes the node's line after `prefix`, and its children's lines after `children_prefix`.
+fn write_tree(
+    expr: &Expr,
+    spans: &SideTable<Range<usize>>,
+    prefix: &str,
+    children_prefix: &str,
+    f: &mut impl Write,
+) -> std::fmt::Result {
+    f.write_str(prefix)?;
+    let children = match expr {
+        Expr::Grouping(v) => {
+            f.write_str("Grouping")?;
+            vec![&*v.expr]
+        }
+        Expr::Unary(v) => {
+            write!(f, "Unary {}", v.unary)?;
+            vec![&*v.expr]
+        }
+        Expr::Binary(v) => {
+            write!(f, "Binary {}", v.operator)?;
+            vec![&*v.left, &*v.right]
+        }
+        Expr::Literal(v) => {
+            f.write_str("Literal ")?;
+            v.write_to(f)?;
+            vec![]
+        }
+        Expr::Error(_) => {
+            f.write_str("Error")?;
+            vec![]
+        }
+    };
+    if let Some(span) = spans.get(expr.id()) {
+        write!(f, " [{span:?}]")?;
+    }
+    f.write_char('\n')?;
+
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        write_tree(
+            child,
+            spans,
+            &format!("{children_prefix}{branch}"),
+            &format!("{children_prefix}{indent}"),
+            f,
+        )?;
+    }
+    Ok(())
+}
+
+/// Prints an AST in reverse Polish notation: operands first, then the operator.
+/// Groupings need no parentheses and are left out. Negation is printed as `~`
+/// to tell it apart from subtraction.
+pub trait AstRpn {
+    fn write_rpn(&self, f: &mut impl Write) -> std::fmt::Result;
+    fn print_ast_rpn(&self) -> String {
+        let mut output = String::new();
+        self.write_rpn(&mut output)
+            .expect("printing RPN returned an error");
+        output
+    }
+}
+
+impl AstRpn for Expr {
+    fn write_rpn(&self, f: &mut impl Write) -> std::fmt::Result {
+        match self {
+            Expr::Grouping(v) => v.expr.write_rpn(f),
+            Expr::Unary(v) => {
+                v.expr.write_rpn(f)?;
+                match v.unary {
+                    Unary::Minus => f.write_str(" ~"),
+                    Unary::Bang => f.write_str(" !"),
+                }
+            }
+            Expr::Binary(v) => {
+                v.left.write_rpn(f)?;
+                f.write_char(' ')?;
+                v.right.write_rpn(f)?;
+                write!(f, " {}", v.operator)
+            }
+            Expr::Literal(v) => v.write_to(f),
+            Expr::Error(_) => f.write_str("<error>"),
+        }
+    }
+}
+
+/// Prints an AST as a Graphviz DOT graph, e.g. for `dot -Tsvg`.
+/// Graph nodes are named after the node ids, which the parser keeps unique.
+pub trait AstDot {
+    fn write_dot(&self, f: &mut impl Write) -> std::fmt::Result;
+    fn print_dot(&self) -> String {
+        let mut output = String::new();
+        self.write_dot(&mut output)
+            .expect("printing DOT returned an error");
+        output
+    }
+}
+
+impl AstDot for Expr {
+    fn write_dot(&self, f: &mut impl Write) -> std::fmt::Result {
+        f.write_str("digraph ast {\n")?;
+        write_dot_node(self, f)?;
+        f.write_str("}\n")
+    }
+}
+
+fn write_dot_node(expr: &Expr, f: &mut impl Write) -> std::fmt::Result {
+    let (label, children) = match expr {
+        Expr::Grouping(v) => ("group".to_string(), vec![&*v.expr]),
+        Expr::Unary(v) => (v.unary.to_string(), vec![&*v.expr]),
+        Expr::Binary(v) => (v.operator.to_string(), vec![&*v.left, &*v.right]),
+        Expr::Literal(v) => (v.print_ast(), vec![]),
+        Expr::Error(_) => ("<error>".to_string(), vec![]),
+    };
+    let id = expr.id().0;
+    write!(f, "    n{id} [label=\"")?;
+    for c in label.chars() {
+        match c {
+            '"' | '\\' => write!(f, "\\{c}")?,
+            '\n' => f.write_str("\\n")?,
+            c => f.write_char(c)?,
         }
     }
+    f.write_str("\"];\n")?;
+    for child in children {
+        writeln!(f, "    n{id} -> n{};", child.id().0)?;
+        write_dot_node(child, f)?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::expression::{BinaryExpr, Expr, GroupingExpr, Literal, Unary::Minus, UnaryExpr};
+    use crate::{
+        expression::{
+            BinaryExpr, Expr, GroupingExpr, Literal, LiteralExpr, NodeId, Operator, SideTable,
+            Unary::Minus, UnaryExpr,
+        },
+        parse::Parser,
+        scanner::scan_tokens,
+    };
 
-    use super::AstPrint;
+    use super::{print_tree, AstDot, AstPrint, AstRpn};
 
     #[test]
     fn binary_plus() {
         let expr = Expr::Binary(BinaryExpr {
-            left: Box::new(Expr::Literal(Literal::Number(1.0))),
+            id: NodeId(2),
+            left: Box::new(Expr::Literal(LiteralExpr {
+                id: NodeId(0),
+                value: Literal::Number(1.0),
+            })),
             operator: crate::expression::Operator::Plus,
-            right: Box::new(Expr::Literal(Literal::Number(3.0))),
+            right: Box::new(Expr::Literal(LiteralExpr {
+                id: NodeId(1),
+                value: Literal::Number(3.0),
+            })),
         });
 
         assert_eq!(expr.print_ast(), "(+ 1 3)")
     }
 
-    #[test]
-    fn from_book() {
-        let expr = Expr::Binary(BinaryExpr {
+    fn from_book_expr() -> Expr {
+        Expr::Binary(BinaryExpr {
+            id: NodeId(4),
             left: Box::new(Expr::Unary(UnaryExpr {
+                id: NodeId(1),
                 unary: Minus,
-                expr: Box::new(Expr::Literal(Literal::Number(123.0))),
+                expr: Box::new(Expr::Literal(LiteralExpr {
+                    id: NodeId(0),
+                    value: Literal::Number(123.0),
+                })),
             })),
             operator: crate::expression::Operator::Multiply,
             right: Box::new(Expr::Grouping(GroupingExpr {
-                expr: Box::new(Expr::Literal(Literal::Number(45.67))),
+                id: NodeId(3),
+                expr: Box::new(Expr::Literal(LiteralExpr {
+                    id: NodeId(2),
+                    value: Literal::Number(45.67),
+                })),
             })),
-        });
+        })
+    }
+
+    #[test]
+    fn from_book() {
+        assert_eq!(from_book_expr().print_ast(), "(* (- 123) (group 45.67))")
+    }
+
+    #[test]
+    fn rpn() {
+        assert_eq!(from_book_expr().print_ast_rpn(), "123 ~ 45.67 *");
+
+        let number = |id, value| {
+            Box::new(Expr::Literal(LiteralExpr {
+                id: NodeId(id),
+                value: Literal::Number(value),
+            }))
+        };
+        let binary = |id, left, operator, right| {
+            Box::new(Expr::Binary(BinaryExpr {
+                id: NodeId(id),
+                left,
+                operator,
+                right,
+            }))
+        };
+        let grouping = |id, expr| {
+            Box::new(Expr::Grouping(GroupingExpr {
+                id: NodeId(id),
+                expr,
+            }))
+        };
+        // (1 + 2) * (4 - 3)
+        let expr = binary(
+            8,
+            grouping(3, binary(2, number(0, 1.0), Operator::Plus, number(1, 2.0))),
+            Operator::Multiply,
+            grouping(
+                7,
+                binary(6, number(4, 4.0), Operator::Minus, number(5, 3.0)),
+            ),
+        );
+        assert_eq!(expr.print_ast_rpn(), "1 2 + 4 3 - *");
+    }
+
+    #[test]
+    fn tree() {
+        let source = "-123 * (45.67)";
+        let mut parser = Parser::new(scan_tokens(source).unwrap());
+        let expr = parser.parse_expression().unwrap();
+        assert_eq!(
+            print_tree(&expr, parser.spans()),
+            "\
+Binary * [0..14]
+├── Unary - [0..4]
+│   └── Literal 123 [1..4]
+└── Grouping [7..14]
+    └── Literal 45.67 [8..13]
+"
+        );
+
+        let expr = Parser::new(scan_tokens("1 + 2 * 3 == !(4)").unwrap())
+            .parse_expression()
+            .unwrap();
+        assert_eq!(
+            print_tree(&expr, &SideTable::default()),
+            "\
+Binary ==
+├── Binary +
+│   ├── Literal 1
+│   └── Binary *
+│       ├── Literal 2
+│       └── Literal 3
+└── Unary !
+    └── Grouping
+        └── Literal 4
+"
+        );
+    }
+
+    #[test]
+    fn dot() {
+        assert_eq!(
+            from_book_expr().print_dot(),
+            "digraph ast {
+    n4 [label=\"*\"];
+    n4 -> n1;
+    n1 [label=\"-\"];
+    n1 -> n0;
+    n0 [label=\"123\"];
+    n4 -> n3;
+    n3 [label=\"group\"];
+    n3 -> n2;
+    n2 [label=\"45.67\"];
+}
+"
+        );
 
-        assert_eq!(expr.print_ast(), "(* (- 123) (group 45.67))")
+        let expr = Expr::Literal(LiteralExpr {
+            id: NodeId(0),
+            value: Literal::String("say \"hi\"\n".to_string()),
+        });
+        assert_eq!(
+            expr.print_dot(),
+            r#"digraph ast {
+    n0 [label="\"say \\\"hi\\\"\\n\""];
+}
+"#
+        );
     }
 }
@@ -0,0 +1,122 @@
+//! Random expression trees for property tests.
+
+use crate::expression::{
+    BinaryExpr, Expr, GroupingExpr, Literal, LiteralExpr, NodeId, Operator, Unary, UnaryExpr,
+};
+
+const OPERATORS: [Operator; 10] = [
+    Operator::Greater,
+    Operator::GreaterEqual,
+    Operator::Less,
+    Operator::LessEqual,
+    Operator::Equal,
+    Operator::NotEqual,
+    Operator::Minus,
+    Operator::Plus,
+    Operator::Divide,
+    Operator::Multiply,
+];
+
+/// Pieces of strings, chosen to look like other atoms and tokens, or to need
+/// escaping or quoting somewhere.
+const STRING_PIECES: [&str; 14] = [
+    "foo", " ", "(", ")", "12", "2.5", "nil", "true", "group", "<error>", "\"", "\\", "\n", "//",
+];
+
+const SPECIAL_NUMBERS: [f64; 4] = [f64::INFINITY, f64::NEG_INFINITY, -0.0, f64::NAN];
+
+/// Generates expressions from a seed, so failures can be reproduced.
+/// Node ids are assigned in post-order, the same way the parser does.
+pub(crate) struct Generator {
+    state: u64,
+    next_id: usize,
+}
+
+impl Generator {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            // xorshift gets stuck at zero
+            state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+            next_id: 0,
+        }
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % n as u64) as usize
+    }
+
+    fn id(&mut self) -> NodeId {
+        self.next_id += 1;
+        NodeId(self.next_id - 1)
+    }
+
+    /// An expression at most `depth` levels deep.
+    pub(crate) fn expr(&mut self, depth: usize) -> Expr {
+        self.next_id = 0;
+        self.node(depth)
+    }
+
+    fn node(&mut self, depth: usize) -> Expr {
+        match self.below(if depth == 0 { 1 } else { 6 }) {
+            0 | 1 => {
+                let value = self.literal();
+                Expr::Literal(LiteralExpr {
+                    id: self.id(),
+                    value,
+                })
+            }
+            2 => {
+                let expr = Box::new(self.node(depth - 1));
+                Expr::Grouping(GroupingExpr {
+                    id: self.id(),
+                    expr,
+                })
+            }
+            3 => {
+                let unary = [Unary::Bang, Unary::Minus][self.below(2)];
+                let expr = Box::new(self.node(depth - 1));
+                Expr::Unary(UnaryExpr {
+                    id: self.id(),
+                    unary,
+                    expr,
+                })
+            }
+            _ => {
+                let left = Box::new(self.node(depth - 1));
+                let operator = OPERATORS[self.below(OPERATORS.len())];
+                let right = Box::new(self.node(depth - 1));
+                Expr::Binary(BinaryExpr {
+                    id: self.id(),
+                    left,
+                    operator,
+                    right,
+                })
+            }
+        }
+    }
+
+    /// Numbers include negative ones, which the parser never produces,
+    /// as well as the infinities, `-0.0` and NaN.
+    fn literal(&mut self) -> Literal {
+        match self.below(8) {
+            0 => Literal::Nil,
+            1 => Literal::Bool(self.below(2) == 0),
+            2 => Literal::String(self.string()),
+            3 => Literal::Number(self.below(1000) as f64 / 8.0),
+            4 => Literal::Number(10f64.powi(self.below(40) as i32 - 20)),
+            5 => Literal::Number(-(self.below(1000) as f64) / 8.0),
+            6 => Literal::Number(SPECIAL_NUMBERS[self.below(SPECIAL_NUMBERS.len())]),
+            _ => Literal::Number(self.below(100) as f64),
+        }
+    }
+
+    /// Up to four pieces, so the empty string comes up too.
+    fn string(&mut self) -> String {
+        (0..self.below(5))
+            .map(|_| STRING_PIECES[self.below(STRING_PIECES.len())])
+            .collect()
+    }
+}
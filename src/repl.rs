@@ -0,0 +1,114 @@
+//! Helpers for the interactive prompt.
+
+use crate::{scanner::TokenStream, token::TokenType};
+
+/// Whether the input so far can't be complete yet: it has unclosed parentheses
+/// or braces, or ends in an operator. The prompt then reads another line before
+/// running it.
+pub fn is_incomplete(source: &str) -> bool {
+    let mut depth = 0i64;
+    let mut last = None;
+    let tokens = TokenStream::new(source)
+        .with_error_tokens()
+        .map(|token| token.expect("error tokens are emitted instead of errors"));
+    for token in tokens {
+        match token.ty {
+            TokenType::LeftParen | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBrace => depth -= 1,
+            TokenType::Eof => break,
+            _ => {}
+        }
+        last = Some(token.ty);
+    }
+    let trailing_operator = matches!(
+        last,
+        Some(
+            TokenType::Comma
+                | TokenType::Dot
+                | TokenType::Minus
+                | TokenType::Plus
+                | TokenType::Slash
+                | TokenType::Star
+                | TokenType::Bang
+                | TokenType::BangEqual
+                | TokenType::Equal
+                | TokenType::EqualEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::And
+                | TokenType::Or
+        )
+    );
+    depth > 0 || trailing_operator
+}
+
+/// A command to the prompt itself, rather than Lox source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command<'a> {
+    /// Run a file as if its source was entered at the prompt.
+    Load(&'a str),
+    /// Write the source entered so far to a file.
+    Save(&'a str),
+}
+
+/// Parses a line starting with `:` as a command, `None` for any other line.
+pub fn parse_command(line: &str) -> Option<Result<Command<'_>, String>> {
+    let line = line.trim().strip_prefix(':')?;
+    let (name, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let argument = argument.trim();
+    let command = match name {
+        "load" | "save" if argument.is_empty() => Err(format!(":{name} needs a file path")),
+        "load" => Ok(Command::Load(argument)),
+        "save" => Ok(Command::Save(argument)),
+        name => Err(format!("unknown command :{name}")),
+    };
+    Some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_incomplete, parse_command, Command};
+
+    #[test]
+    fn incomplete() {
+        assert!(is_incomplete("(1 +\n"));
+        assert!(is_incomplete("1 + (2 * (3\n  - 4)\n"));
+        assert!(is_incomplete("1 +\n"));
+        assert!(is_incomplete("1 + // more to come\n"));
+        assert!(is_incomplete("fun f() {\n"));
+        assert!(is_incomplete("!\n"));
+    }
+
+    #[test]
+    fn complete() {
+        assert!(!is_incomplete("1 + 2\n"));
+        assert!(!is_incomplete("(1 +\n 2)\n"));
+        assert!(!is_incomplete("\n"));
+        assert!(!is_incomplete("1 + 2)\n"));
+        assert!(!is_incomplete("\"(\"\n"));
+        assert!(!is_incomplete("1 + @\n"));
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse_command("1 + 2\n"), None);
+        assert_eq!(
+            parse_command(":load lib.lox\n"),
+            Some(Ok(Command::Load("lib.lox")))
+        );
+        assert_eq!(
+            parse_command("  :save  my session.lox \n"),
+            Some(Ok(Command::Save("my session.lox")))
+        );
+        assert_eq!(
+            parse_command(":load\n"),
+            Some(Err(":load needs a file path".to_string()))
+        );
+        assert_eq!(
+            parse_command(":quit\n"),
+            Some(Err("unknown command :quit".to_string()))
+        );
+    }
+}
@@ -1,47 +1,324 @@
-use std::borrow::BorrowMut;
+use std::{fmt, iter::Enumerate, ops::Range, str::Lines};
 
-use itertools::Itertools;
+use crate::{
+    symbol::Interner,
+    token::{Token, TokenType, Trivia, TriviaKind},
+};
 
-use crate::token::{Token, TokenType};
+#[derive(Debug, Clone, PartialEq, parse_display::Display)]
+pub enum ScanErrorKind {
+    #[display("Unterminated string")]
+    UnterminatedString,
+    #[display("Invalid character: '{0}'")]
+    InvalidCharacter(char),
+    #[display("Invalid number literal: '{0}'")]
+    InvalidNumber(String),
+}
 
-pub fn scan_tokens(source: &str) -> Result<impl Iterator<Item = Token>, anyhow::Error> {
-    let mut tokens = Vec::new();
-    let mut total_lines = 0;
-    for (line_no, line) in source.lines().enumerate() {
-        total_lines = line_no;
-        let (line_remainder, tokens_in_line) = scan_line(line)?;
-        assert!(
-            line_remainder.is_empty(),
-            "Not every character of line: '{line}' was consumed. Leftovers: '{line_remainder}'"
-        );
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    /// Byte offsets of the input that could not be scanned.
+    pub span: Range<usize>,
+    /// 0-based, displayed 1-based like editors do.
+    pub line: usize,
+}
 
-        tokens.extend(tokens_in_line.into_iter().map(|t| Token {
-            ty: t,
-            line: line_no,
-        }));
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}] Error: {}", self.line + 1, self.kind)
     }
+}
 
-    tokens.push(Token {
-        ty: crate::token::TokenType::Eof,
-        line: total_lines,
-    });
-    Ok(tokens.into_iter())
+impl std::error::Error for ScanError {}
+
+/// Lazily scans tokens from the source, one line at a time.
+/// After an error the offending input is skipped and scanning resumes.
+pub struct TokenStream<'a> {
+    source: &'a str,
+    lines: Enumerate<Lines<'a>>,
+    first_line: usize,
+    line: &'a str,
+    line_no: usize,
+    finished: bool,
+    trivia: bool,
+    error_tokens: bool,
+    last_end: usize,
+    interner: Interner,
 }
 
-fn scan_line(mut input_line: &str) -> Result<(&str, Vec<TokenType>), anyhow::Error> {
-    let mut tokens = Vec::new();
-    loop {
-        let (line, maybe_token) = scan_token(input_line)?;
-        input_line = line;
-        if let Some(token) = maybe_token {
-            tokens.push(token);
+impl<'a> TokenStream<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let mut stream = Self {
+            source,
+            lines: "".lines().enumerate(),
+            first_line: 0,
+            line: "",
+            line_no: 0,
+            finished: false,
+            trivia: false,
+            error_tokens: false,
+            last_end: 0,
+            interner: Interner::default(),
+        };
+        stream.seek(0, 0, 0);
+        stream
+    }
+
+    /// Continue scanning from the start of the line at byte `offset`, which is line `line_no`.
+    /// `last_end` is where the previous token ended.
+    fn seek(&mut self, offset: usize, line_no: usize, last_end: usize) {
+        self.lines = self.source[offset..].lines().enumerate();
+        if offset == 0 && self.source.starts_with("#!") {
+            // Skip the shebang line of an executable script
+            self.lines.next();
+        }
+        // A full scan never reaches the empty line after a trailing newline,
+        // so the end of input stays on the line before it
+        let line_no = if offset > 0 && offset == self.source.len() {
+            line_no - 1
+        } else {
+            line_no
+        };
+        self.first_line = line_no;
+        self.line = "";
+        self.line_no = line_no;
+        self.finished = false;
+        self.last_end = last_end;
+    }
+
+    /// Attach the whitespace and comments preceding every token to it as trivia,
+    /// so that the source can be reproduced from the tokens.
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = true;
+        self
+    }
+
+    /// Emit `TokenType::Error` tokens covering the input that could not be scanned
+    /// instead of returning errors, so that every part of the source is covered by a token.
+    pub fn with_error_tokens(mut self) -> Self {
+        self.error_tokens = true;
+        self
+    }
+
+    /// Intern identifiers into `interner`, e.g. to share symbols across the
+    /// sources of a REPL session. Each stream starts with an empty one otherwise.
+    pub fn with_interner(mut self, interner: Interner) -> Self {
+        self.interner = interner;
+        self
+    }
+
+    /// The interner the symbols of identifier tokens belong to.
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    pub fn into_interner(self) -> Interner {
+        self.interner
+    }
+
+    fn token(&mut self, ty: TokenType, lexeme: &'a str) -> Token<'a> {
+        // Lexemes are always slices of the source
+        let start = lexeme.as_ptr() as usize - self.source.as_ptr() as usize;
+        let trivia = if self.trivia {
+            split_trivia(&self.source[self.last_end..start], self.last_end == 0)
         } else {
-            return Ok((line, tokens));
+            Vec::new()
+        };
+        self.last_end = start + lexeme.len();
+        Token {
+            ty,
+            lexeme,
+            span: start..start + lexeme.len(),
+            line: self.line_no,
+            trivia,
         }
     }
 }
 
-fn scan_token(input: &str) -> Result<(&str, Option<TokenType>), anyhow::Error> {
+impl<'a> Iterator for TokenStream<'a> {
+    type Item = Result<Token<'a>, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            let input = self.line.trim_start();
+            match scan_token(input, &mut self.interner) {
+                Ok((rest, Some(ty))) => {
+                    self.line = rest;
+                    let lexeme = &input[..input.len() - rest.len()];
+                    return Some(Ok(self.token(ty, lexeme)));
+                }
+                Ok((_, None)) => match self.lines.next() {
+                    Some((line_no, line)) => {
+                        self.line_no = self.first_line + line_no;
+                        self.line = line;
+                    }
+                    None => {
+                        self.finished = true;
+                        let end = &self.source[self.source.len()..];
+                        return Some(Ok(self.token(TokenType::Eof, end)));
+                    }
+                },
+                Err(kind) => {
+                    self.line = match kind {
+                        ScanErrorKind::InvalidCharacter(c) => &input[c.len_utf8()..],
+                        ScanErrorKind::UnterminatedString => "",
+                        ScanErrorKind::InvalidNumber(ref literal) => &input[literal.len()..],
+                    };
+                    let lexeme = &input[..input.len() - self.line.len()];
+                    if self.error_tokens {
+                        return Some(Ok(self.token(TokenType::Error(kind), lexeme)));
+                    }
+                    let start = lexeme.as_ptr() as usize - self.source.as_ptr() as usize;
+                    return Some(Err(ScanError {
+                        kind,
+                        span: start..start + lexeme.len(),
+                        line: self.line_no,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// Replacement of the `range` of a source with `text`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit<'t> {
+    pub range: Range<usize>,
+    pub text: &'t str,
+}
+
+impl TextEdit<'_> {
+    pub fn apply(&self, source: &str) -> String {
+        [
+            &source[..self.range.start],
+            self.text,
+            &source[self.range.end..],
+        ]
+        .concat()
+    }
+}
+
+impl<'a> TokenStream<'a> {
+    /// Re-scan the source after an edit. `tokens` are the tokens of the source before `edit`
+    /// was applied and the stream is over the edited source.
+    ///
+    /// Only the lines touched by the edit are scanned again: tokens before them are reused,
+    /// and scanning stops as soon as it reaches a token that also started a token before
+    /// the edit, since scanning is deterministic from there on. The stream should use the
+    /// interner `tokens` were scanned with, so that their symbols stay valid.
+    pub fn rescan(
+        mut self,
+        tokens: &[Token<'_>],
+        edit: &TextEdit,
+    ) -> Result<Vec<Token<'a>>, ScanError> {
+        let source = self.source;
+        let line_start = source[..edit.range.start].rfind('\n').map_or(0, |i| i + 1);
+        let reused_before = tokens
+            .iter()
+            .take_while(|t| t.span.start < line_start && t.ty != TokenType::Eof)
+            .count();
+        let mut result = tokens[..reused_before]
+            .iter()
+            .map(|t| rebase(t, source, 0, 0))
+            .collect::<Vec<_>>();
+
+        let last_end = result.last().map_or(0, |t| t.span.end);
+        let line_no = source[..line_start].matches('\n').count();
+        self.seek(line_start, line_no, last_end);
+
+        let edit_end = edit.range.start + edit.text.len();
+        let shift = edit.text.len() as isize - edit.range.len() as isize;
+        for token in self.by_ref() {
+            let token = token?;
+            if token.span.start >= edit_end {
+                let old_start = (token.span.start as isize - shift) as usize;
+                let reusable = tokens[reused_before..]
+                    .binary_search_by_key(&old_start, |t| t.span.start)
+                    .ok()
+                    .map(|i| reused_before + i)
+                    .filter(|i| tokens[*i].ty == token.ty);
+                if let Some(i) = reusable {
+                    let line_shift = token.line as isize - tokens[i].line as isize;
+                    result.push(token);
+                    result.extend(
+                        tokens[i + 1..]
+                            .iter()
+                            .map(|t| rebase(t, source, shift, line_shift)),
+                    );
+                    return Ok(result);
+                }
+            }
+            result.push(token);
+        }
+        Ok(result)
+    }
+}
+
+/// Moves a token scanned from another version of the source into `source`.
+fn rebase<'a>(token: &Token<'_>, source: &'a str, shift: isize, line_shift: isize) -> Token<'a> {
+    let start = (token.span.start as isize + shift) as usize;
+    let span = start..start + token.span.len();
+
+    // Trivia directly precedes the lexeme
+    let mut trivia_start = start - token.trivia.iter().map(|t| t.text.len()).sum::<usize>();
+    let trivia = token
+        .trivia
+        .iter()
+        .map(|t| {
+            let text = &source[trivia_start..trivia_start + t.text.len()];
+            trivia_start += t.text.len();
+            Trivia { kind: t.kind, text }
+        })
+        .collect();
+
+    Token {
+        ty: token.ty.clone(),
+        lexeme: &source[span.clone()],
+        span,
+        line: (token.line as isize + line_shift) as usize,
+        trivia,
+    }
+}
+
+fn split_trivia(mut gap: &str, at_source_start: bool) -> Vec<Trivia<'_>> {
+    let mut trivia = Vec::new();
+    while let Some(c) = gap.chars().next() {
+        let till_line_end = gap.find(['\r', '\n']).unwrap_or(gap.len());
+        let (kind, len) = if c.is_whitespace() {
+            let len = gap.find(|c: char| !c.is_whitespace()).unwrap_or(gap.len());
+            (TriviaKind::Whitespace, len)
+        } else if gap.starts_with("//") {
+            (TriviaKind::Comment, till_line_end)
+        } else if at_source_start && trivia.is_empty() && gap.starts_with("#!") {
+            (TriviaKind::Shebang, till_line_end)
+        } else {
+            let len = gap
+                .char_indices()
+                .skip(1)
+                .find(|(i, c)| c.is_whitespace() || gap[*i..].starts_with("//"))
+                .map_or(gap.len(), |(i, _)| i);
+            (TriviaKind::Skipped, len)
+        };
+        let (text, rest) = gap.split_at(len);
+        trivia.push(Trivia { kind, text });
+        gap = rest;
+    }
+    trivia
+}
+
+pub fn scan_tokens(source: &str) -> Result<impl Iterator<Item = Token<'_>>, anyhow::Error> {
+    let tokens = TokenStream::new(source).collect::<Result<Vec<_>, _>>()?;
+    Ok(tokens.into_iter())
+}
+
+fn scan_token<'i>(
+    input: &'i str,
+    interner: &mut Interner,
+) -> Result<(&'i str, Option<TokenType>), ScanErrorKind> {
     let input = input.trim_start();
     if input.is_empty() {
         return Ok((input, None));
@@ -112,41 +389,29 @@ fn scan_token(input: &str) -> Result<(&str, Option<TokenType>), anyhow::Error> {
             }
             '"' => {
                 // TODO: figure out support for multi-line strings
-                let mut closed = false;
-                let str_content: String = chars
-                    .borrow_mut()
-                    .take_while(|c| {
-                        closed = *c == '"';
-                        !closed
-                    })
-                    .collect();
-                if !closed {
-                    return Err(anyhow::anyhow!("Unterminated string"));
+                if !chars.any(|c| c == '"') {
+                    return Err(ScanErrorKind::UnterminatedString);
                 }
-                Some(TokenType::String(str_content))
+                Some(TokenType::String)
             }
             '0'..='9' => {
-                let digits = 1 + chars.peeking_take_while(|c| c.is_ascii_digit()).count();
-                let mut peek = chars.clone();
-                let fractional_chars = if let Some('.') = peek.next() {
-                    let digits = peek.take_while(|c| c.is_ascii_digit()).count();
-                    if digits > 0 {
-                        digits + 1
-                    } else {
-                        0
-                    }
-                } else {
-                    0
-                };
-
-                let num = input[..digits + fractional_chars].parse::<f64>()?;
-                for _ in 0..fractional_chars {
-                    chars.next();
+                if let Some(radix) = radix_prefix(input) {
+                    return scan_radix_number(input, radix);
+                }
+                let len = number_len(input);
+                // A number running into an identifier, like "123abc", is malformed
+                let (literal, rest) = input.split_at(len + word_len(&input[len..]));
+                if literal.len() > len || !valid_separators(literal, 10) {
+                    return Err(ScanErrorKind::InvalidNumber(literal.to_string()));
                 }
-                Some(TokenType::Number(num))
+                let num = literal
+                    .replace('_', "")
+                    .parse::<f64>()
+                    .expect("scanned digits form a valid number");
+                return Ok((rest, Some(TokenType::Number(num))));
             }
-            c if !(c.is_alphanumeric() || c == '_') => {
-                return Err(anyhow::anyhow!("Invalid character: '{c}'"));
+            c if !is_identifier_start(c) => {
+                return Err(ScanErrorKind::InvalidCharacter(c));
             }
             _ => None,
         };
@@ -157,50 +422,420 @@ fn scan_token(input: &str) -> Result<(&str, Option<TokenType>), anyhow::Error> {
     }
 
     // Try as a keyword or an identifier:
-    let pos_word_end = input
+    let (word, rest) = input.split_at(word_len(input));
+    let token =
+        TokenType::keyword(word).unwrap_or_else(|| TokenType::Identifier(interner.intern(word)));
+    Ok((rest, Some(token)))
+}
+
+/// Identifiers follow UAX #31: an XID_Start character or '_',
+/// followed by XID_Continue characters.
+fn is_identifier_start(c: char) -> bool {
+    unicode_ident::is_xid_start(c) || c == '_'
+}
+
+/// Length in bytes of the XID_Continue characters at the start of `input`.
+fn word_len(input: &str) -> usize {
+    input
+        .chars()
+        .take_while(|c| unicode_ident::is_xid_continue(*c))
+        .map(char::len_utf8)
+        .sum()
+}
+
+/// Length of the number literal at the start of `input`: integer digits,
+/// an optional fraction and an optional exponent (`1`, `1.5`, `2.5e-3`).
+/// Digit separators are included and validated separately.
+fn number_len(input: &str) -> usize {
+    let digits = |s: &str| {
+        s.bytes()
+            .take_while(|b| b.is_ascii_digit() || *b == b'_')
+            .count()
+    };
+
+    let mut len = digits(input);
+    if let Some(fraction) = input[len..].strip_prefix('.') {
+        let fraction_digits = digits(fraction);
+        if fraction_digits > 0 {
+            len += 1 + fraction_digits;
+        }
+    }
+    if let Some(exponent) = input[len..].strip_prefix(['e', 'E']) {
+        let sign = usize::from(exponent.starts_with(['+', '-']));
+        let exponent_digits = digits(&exponent[sign..]);
+        if exponent_digits > 0 {
+            len += 1 + sign + exponent_digits;
+        }
+    }
+    len
+}
+
+/// Radix of a `0x` (hexadecimal) or `0b` (binary) prefixed literal.
+fn radix_prefix(input: &str) -> Option<u32> {
+    match input.get(..2)? {
+        "0x" | "0X" => Some(16),
+        "0b" | "0B" => Some(2),
+        _ => None,
+    }
+}
+
+fn scan_radix_number(input: &str, radix: u32) -> Result<(&str, Option<TokenType>), ScanErrorKind> {
+    let len = 2 + word_len(&input[2..]);
+    let (literal, rest) = input.split_at(len);
+    let digits = &literal[2..];
+    if digits.is_empty()
+        || !digits.chars().all(|c| c.is_digit(radix) || c == '_')
+        || !valid_separators(digits, radix)
+    {
+        return Err(ScanErrorKind::InvalidNumber(literal.to_string()));
+    }
+
+    let num = digits
         .chars()
-        .take_while_ref(|c| c.is_alphanumeric() || *c == '_')
-        .count();
-    let word = &input[..pos_word_end];
-    let token = word.parse::<TokenType>()?;
-    Ok((&input[pos_word_end..], Some(token)))
+        .filter_map(|c| c.to_digit(radix))
+        .fold(0.0, |num, digit| num * radix as f64 + digit as f64);
+    Ok((rest, Some(TokenType::Number(num))))
+}
+
+/// Underscores may only separate digits: `1_000` and `0xFF_FF` are fine,
+/// while `1_`, `1_.5`, `1._5`, `1_e5` and `0x_FF` are not.
+fn valid_separators(literal: &str, radix: u32) -> bool {
+    let chars = literal.chars().collect::<Vec<_>>();
+    let is_digit_or_separator =
+        |c: Option<&char>| c.is_some_and(|c| *c == '_' || c.is_digit(radix));
+    chars
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c == '_')
+        .all(|(i, _)| {
+            i > 0
+                && is_digit_or_separator(chars.get(i - 1))
+                && is_digit_or_separator(chars.get(i + 1))
+        })
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{scanner::scan_token, token::TokenType};
+    use crate::{
+        scanner::{ScanError, ScanErrorKind, TextEdit, TokenStream},
+        symbol::Interner,
+        token::{Token, TokenType, Trivia, TriviaKind},
+    };
 
-    use super::scan_line;
+    fn scan_token(input: &str) -> Result<(&str, Option<TokenType>), ScanErrorKind> {
+        super::scan_token(input, &mut Interner::default())
+    }
+
+    /// The rest of the input after an identifier, and the identifier's name.
+    fn scan_name(input: &str) -> (&str, String) {
+        let mut interner = Interner::default();
+        match super::scan_token(input, &mut interner) {
+            Ok((rest, Some(TokenType::Identifier(symbol)))) => {
+                (rest, interner.resolve(symbol).to_string())
+            }
+            token => panic!("expected an identifier, got {token:?}"),
+        }
+    }
+
+    fn scan_line(line: &str) -> Vec<TokenType> {
+        TokenStream::new(line)
+            .map(|t| t.unwrap().ty)
+            .filter(|ty| *ty != TokenType::Eof)
+            .collect()
+    }
 
     #[test]
     fn scanning_line() {
         assert_eq!(
-            scan_line("()").unwrap(),
-            ("", vec![TokenType::LeftParen, TokenType::RightParen])
+            scan_line("()"),
+            vec![TokenType::LeftParen, TokenType::RightParen]
         );
 
         assert_eq!(
-            scan_line("(    )").unwrap(),
-            ("", vec![TokenType::LeftParen, TokenType::RightParen])
+            scan_line("(    )"),
+            vec![TokenType::LeftParen, TokenType::RightParen]
+        );
+        assert_eq!(
+            scan_line("!*+-/=<> <= == // operators"),
+            vec![
+                TokenType::Bang,
+                TokenType::Star,
+                TokenType::Plus,
+                TokenType::Minus,
+                TokenType::Slash,
+                TokenType::Equal,
+                TokenType::Less,
+                TokenType::Greater,
+                TokenType::LessEqual,
+                TokenType::EqualEqual,
+            ]
         );
+    }
+
+    #[test]
+    fn token_stream_lines() {
+        let tokens = TokenStream::new("(\n\n)").collect::<Result<Vec<_>, _>>();
         assert_eq!(
-            scan_line("!*+-/=<> <= == // operators").unwrap(),
-            (
-                "",
-                vec![
-                    TokenType::Bang,
-                    TokenType::Star,
-                    TokenType::Plus,
-                    TokenType::Minus,
-                    TokenType::Slash,
-                    TokenType::Equal,
-                    TokenType::Less,
-                    TokenType::Greater,
-                    TokenType::LessEqual,
-                    TokenType::EqualEqual,
-                ]
+            tokens.unwrap(),
+            vec![
+                Token {
+                    ty: TokenType::LeftParen,
+                    lexeme: "(",
+                    span: 0..1,
+                    line: 0,
+                    trivia: vec![],
+                },
+                Token {
+                    ty: TokenType::RightParen,
+                    lexeme: ")",
+                    span: 3..4,
+                    line: 2,
+                    trivia: vec![],
+                },
+                Token {
+                    ty: TokenType::Eof,
+                    lexeme: "",
+                    span: 4..4,
+                    line: 2,
+                    trivia: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn token_lexemes() {
+        let source = "var whilee = \"a b\" >= 12.5; // comment";
+        let tokens = TokenStream::new(source)
+            .map(|t| t.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens.iter().map(|t| t.lexeme).collect::<Vec<_>>(),
+            vec!["var", "whilee", "=", "\"a b\"", ">=", "12.5", ";", ""]
+        );
+        for token in tokens {
+            assert_eq!(&source[token.span], token.lexeme);
+        }
+    }
+
+    #[test]
+    fn skip_shebang() {
+        let tokens = TokenStream::new("#!/usr/bin/env rlox\n1 + 2")
+            .map(|t| t.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens.iter().map(|t| (&t.ty, t.line)).collect::<Vec<_>>(),
+            vec![
+                (&TokenType::Number(1.0), 1),
+                (&TokenType::Plus, 1),
+                (&TokenType::Number(2.0), 1),
+                (&TokenType::Eof, 1),
+            ]
+        );
+
+        // Only a shebang on the very first line is skipped
+        assert!(TokenStream::new("\n#!/usr/bin/env rlox").any(|t| t.is_err()));
+    }
+
+    #[test]
+    fn trivia() {
+        let source = "#!/usr/bin/env rlox\n// leading\n  foo // trailing\r\n@ 1\n";
+        let tokens = TokenStream::new(source)
+            .with_trivia()
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens[0].trivia,
+            vec![
+                Trivia {
+                    kind: TriviaKind::Shebang,
+                    text: "#!/usr/bin/env rlox"
+                },
+                Trivia {
+                    kind: TriviaKind::Whitespace,
+                    text: "\n"
+                },
+                Trivia {
+                    kind: TriviaKind::Comment,
+                    text: "// leading"
+                },
+                Trivia {
+                    kind: TriviaKind::Whitespace,
+                    text: "\n  "
+                },
+            ]
+        );
+        assert_eq!(
+            tokens[1].trivia.iter().map(|t| t.kind).collect::<Vec<_>>(),
+            vec![
+                TriviaKind::Whitespace,
+                TriviaKind::Comment,
+                TriviaKind::Whitespace,
+                TriviaKind::Skipped,
+                TriviaKind::Whitespace,
+            ]
+        );
+
+        let reconstructed = tokens
+            .iter()
+            .flat_map(|t| t.trivia.iter().map(|t| t.text).chain([t.lexeme]))
+            .collect::<String>();
+        assert_eq!(reconstructed, source);
+
+        // Without trivia mode nothing is recorded
+        assert!(TokenStream::new(source).all(|t| t.map_or(true, |t| t.trivia.is_empty())));
+    }
+
+    fn assert_rescan(source: &str, edit: TextEdit, trivia: bool) {
+        let stream = |source| {
+            let stream = TokenStream::new(source);
+            if trivia {
+                stream.with_trivia()
+            } else {
+                stream
+            }
+        };
+        let mut old = stream(source);
+        let tokens = old.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        let interner = old.into_interner();
+        let edited = edit.apply(source);
+        let expected = stream(&edited)
+            .with_interner(interner.clone())
+            .collect::<Result<Vec<_>, _>>();
+        let rescanned = stream(&edited)
+            .with_interner(interner)
+            .rescan(&tokens, &edit);
+        assert_eq!(rescanned, expected);
+    }
+
+    #[test]
+    fn rescan() {
+        let source = "#!/usr/bin/env rlox\n1 + 2; // one\n\n  \"a\" == b;\nfoo(bar)\n";
+        for trivia in [false, true] {
+            for (range, text) in [
+                (0..0, "x "),
+                (20..21, "10"),
+                (23..25, "2.5 * 4\n\n"),
+                (26..26, "\"unterminated"),
+                (28..33, ""),
+                (36..42, "\"a\nb\""),
+                (40..40, "  "),
+                (51..51, "\n;"),
+                (0..source.len(), ""),
+                (source.len()..source.len(), "baz"),
+            ] {
+                assert_rescan(source, TextEdit { range, text }, trivia);
+            }
+            // Emptying the last line leaves the source ending in a newline
+            for (source, range) in [("1 +\n2", 4..5), ("1\n\n2", 3..4)] {
+                assert_rescan(source, TextEdit { range, text: "" }, trivia);
+            }
+        }
+    }
+
+    #[test]
+    fn rescan_reuses_tokens_after_edit() {
+        let source = "a\nb\nc";
+        let tokens = TokenStream::new(source)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let edit = TextEdit {
+            range: 2..3,
+            text: "x\ny",
+        };
+        let edited = edit.apply(source);
+        let rescanned = TokenStream::new(&edited).rescan(&tokens, &edit).unwrap();
+        assert_eq!(
+            rescanned
+                .iter()
+                .map(|t| (t.lexeme, t.span.clone(), t.line))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", 0..1, 0),
+                ("x", 2..3, 1),
+                ("y", 4..5, 2),
+                ("c", 6..7, 3),
+                ("", 7..7, 3)
+            ]
+        );
+
+        assert!(TokenStream::new("@")
+            .rescan(
+                &tokens,
+                &TextEdit {
+                    range: 0..source.len(),
+                    text: "@"
+                }
             )
+            .is_err());
+    }
+
+    #[test]
+    fn error_tokens() {
+        let source = "( @ 123abc ) \"foo\n+";
+        let tokens = TokenStream::new(source)
+            .with_error_tokens()
+            .with_trivia()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|t| (&t.ty, t.lexeme, t.span.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (&TokenType::LeftParen, "(", 0..1),
+                (
+                    &TokenType::Error(ScanErrorKind::InvalidCharacter('@')),
+                    "@",
+                    2..3
+                ),
+                (
+                    &TokenType::Error(ScanErrorKind::InvalidNumber("123abc".to_string())),
+                    "123abc",
+                    4..10
+                ),
+                (&TokenType::RightParen, ")", 11..12),
+                (
+                    &TokenType::Error(ScanErrorKind::UnterminatedString),
+                    "\"foo",
+                    13..17
+                ),
+                (&TokenType::Plus, "+", 18..19),
+                (&TokenType::Eof, "", 19..19),
+            ]
+        );
+        assert!(tokens
+            .iter()
+            .flat_map(|t| &t.trivia)
+            .all(|t| t.kind == TriviaKind::Whitespace));
+    }
+
+    #[test]
+    fn token_stream_resumes_after_error() {
+        let mut stream = TokenStream::new("( @ )\n\"foo\n+");
+        assert_eq!(stream.next().unwrap().unwrap().ty, TokenType::LeftParen);
+        assert_eq!(
+            stream.next().unwrap(),
+            Err(ScanError {
+                kind: ScanErrorKind::InvalidCharacter('@'),
+                span: 2..3,
+                line: 0
+            })
+        );
+        assert_eq!(stream.next().unwrap().unwrap().ty, TokenType::RightParen);
+        assert_eq!(
+            stream.next().unwrap(),
+            Err(ScanError {
+                kind: ScanErrorKind::UnterminatedString,
+                span: 6..10,
+                line: 1
+            })
         );
+        assert_eq!(stream.next().unwrap().unwrap().ty, TokenType::Plus);
+        assert_eq!(stream.next().unwrap().unwrap().ty, TokenType::Eof);
+        assert!(stream.next().is_none());
     }
 
     #[test]
@@ -320,13 +955,7 @@ mod tests {
     #[test]
     fn scan_string() {
         let token = scan_token(r#""some string" some leftovers"#);
-        assert_eq!(
-            token.unwrap(),
-            (
-                " some leftovers",
-                Some(TokenType::String("some string".to_string()))
-            )
-        );
+        assert_eq!(token.unwrap(), (" some leftovers", Some(TokenType::String)));
 
         let token = scan_token(r#""an unterminated string"#);
         assert!(token.is_err());
@@ -358,32 +987,138 @@ mod tests {
     }
 
     #[test]
-    fn scan_identifier() {
+    fn scan_scientific_number() {
+        assert_eq!(
+            scan_token("1e9").unwrap(),
+            ("", Some(TokenType::Number(1e9)))
+        );
+        assert_eq!(
+            scan_token("2.5e-3;").unwrap(),
+            (";", Some(TokenType::Number(2.5e-3)))
+        );
+        assert_eq!(
+            scan_token("1E+6").unwrap(),
+            ("", Some(TokenType::Number(1e6)))
+        );
+        assert_eq!(
+            scan_token("1.5e3.2").unwrap(),
+            (".2", Some(TokenType::Number(1500.0)))
+        );
+        // Without exponent digits the "e" makes the literal malformed
+        assert_eq!(
+            scan_token("1e"),
+            Err(ScanErrorKind::InvalidNumber("1e".to_string()))
+        );
+        assert_eq!(
+            scan_token("1e+"),
+            Err(ScanErrorKind::InvalidNumber("1e".to_string()))
+        );
+        assert_eq!(
+            scan_token("1.e5").unwrap(),
+            (".e5", Some(TokenType::Number(1.0)))
+        );
+    }
+
+    #[test]
+    fn scan_radix_number() {
+        assert_eq!(
+            scan_token("0xFF").unwrap(),
+            ("", Some(TokenType::Number(255.0)))
+        );
+        assert_eq!(
+            scan_token("0Xff+1").unwrap(),
+            ("+1", Some(TokenType::Number(255.0)))
+        );
         assert_eq!(
-            scan_token("eof").unwrap(),
-            ("", Some(TokenType::Identifier("eof".to_string())))
+            scan_token("0b1010").unwrap(),
+            ("", Some(TokenType::Number(10.0)))
         );
         assert_eq!(
-            scan_token("Eof").unwrap(),
-            ("", Some(TokenType::Identifier("Eof".to_string())))
+            scan_token("0B11.5").unwrap(),
+            (".5", Some(TokenType::Number(3.0)))
         );
+        for literal in ["0x", "0b", "0xG", "0b102", "0x1p"] {
+            assert_eq!(
+                scan_token(literal),
+                Err(ScanErrorKind::InvalidNumber(literal.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn scan_number_with_separators() {
         assert_eq!(
-            scan_token("foo").unwrap(),
-            ("", Some(TokenType::Identifier("foo".to_string())))
+            scan_token("1_000_000").unwrap(),
+            ("", Some(TokenType::Number(1_000_000.0)))
         );
         assert_eq!(
-            scan_token("_").unwrap(),
-            ("", Some(TokenType::Identifier("_".to_string())))
+            scan_token("1_0.2_5e1_0").unwrap(),
+            ("", Some(TokenType::Number(10.25e10)))
         );
         assert_eq!(
-            scan_token("   _123").unwrap(),
-            ("", Some(TokenType::Identifier("_123".to_string())))
+            scan_token("0xFF_FF").unwrap(),
+            ("", Some(TokenType::Number(65535.0)))
         );
         assert_eq!(
-            scan_token("_for").unwrap(),
-            ("", Some(TokenType::Identifier("_for".to_string())))
+            scan_token("0b1__0").unwrap(),
+            ("", Some(TokenType::Number(2.0)))
         );
+        for literal in [
+            "1_", "1_.5", "1._5", "1_e5", "1e_5", "0x_FF", "0xFF_", "0b_",
+        ] {
+            assert_eq!(
+                scan_token(literal),
+                Err(ScanErrorKind::InvalidNumber(literal.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn scan_identifier() {
+        assert_eq!(scan_name("eof"), ("", "eof".to_string()));
+        assert_eq!(scan_name("Eof"), ("", "Eof".to_string()));
+        assert_eq!(scan_name("foo"), ("", "foo".to_string()));
+        assert_eq!(scan_name("_"), ("", "_".to_string()));
+        assert_eq!(scan_name("   _123"), ("", "_123".to_string()));
+        assert_eq!(scan_name("_for"), ("", "_for".to_string()));
+    }
+    #[test]
+    fn scan_unicode_identifier() {
+        assert_eq!(scan_name("café = 1"), (" = 1", "café".to_string()));
+        assert_eq!(scan_name("変数"), ("", "変数".to_string()));
+        assert_eq!(scan_name("x\u{0301}"), ("", "x\u{0301}".to_string()));
+        // Words that parse as floats are still identifiers
+        assert_eq!(scan_name("inf"), ("", "inf".to_string()));
+        assert_eq!(scan_name("NaN"), ("", "NaN".to_string()));
+        // Not valid identifier starts
+        for c in ['\u{0301}', '²', '€'] {
+            assert_eq!(
+                scan_token(&c.to_string()),
+                Err(ScanErrorKind::InvalidCharacter(c))
+            );
+        }
     }
+
+    #[test]
+    fn scan_number_followed_by_identifier() {
+        assert_eq!(
+            scan_token("123abc"),
+            Err(ScanErrorKind::InvalidNumber("123abc".to_string()))
+        );
+        assert_eq!(
+            scan_token("1.5_x"),
+            Err(ScanErrorKind::InvalidNumber("1.5_x".to_string()))
+        );
+        assert_eq!(
+            scan_token("0xFFz"),
+            Err(ScanErrorKind::InvalidNumber("0xFFz".to_string()))
+        );
+        assert_eq!(
+            scan_token("12.foo").unwrap(),
+            (".foo", Some(TokenType::Number(12.0)))
+        );
+    }
+
     #[test]
     fn scan_keyword() {
         assert_eq!(scan_token("and").unwrap(), ("", Some(TokenType::And)));
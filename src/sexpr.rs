@@ -0,0 +1,345 @@
+//! Reads ASTs in the s-expression form written by `AstPrint`, e.g. `(+ 1 (group 2))`,
+//! so that trees can be written by hand in tests and tools.
+//!
+//! Atoms are numbers, `true`, `false`, `nil`, `<error>` and strings in double
+//! quotes, where `\"`, `\\` and `\n` stand for a quote, a backslash and a line
+//! break. Node ids are assigned children first, in the same order as the parser
+//! assigns them.
+
+use std::fmt;
+
+use crate::{
+    expression::{
+        BinaryExpr, Expr, GroupingExpr, Literal, LiteralExpr, NodeId, Operator, Unary, UnaryExpr,
+    },
+    parse::DEFAULT_MAX_DEPTH,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SexprError {
+    pub message: String,
+    /// Byte offset of the error in the input.
+    pub offset: usize,
+}
+
+impl fmt::Display for SexprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for SexprError {}
+
+pub fn parse(input: &str) -> Result<Expr, SexprError> {
+    let mut reader = Reader {
+        input,
+        offset: 0,
+        next_id: 0,
+        depth: 0,
+    };
+    let expr = reader.expr()?;
+    match reader.next()? {
+        None => Ok(expr),
+        Some((atom, offset)) => Err(SexprError {
+            message: format!("unexpected '{atom}' after the expression"),
+            offset,
+        }),
+    }
+}
+
+struct Reader<'a> {
+    input: &'a str,
+    offset: usize,
+    next_id: usize,
+    /// How many lists are open, limited like the parser's nesting depth.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// The next parenthesis or atom and its offset, `None` at the end of input.
+    fn next(&mut self) -> Result<Option<(&'a str, usize)>, SexprError> {
+        let rest = &self.input[self.offset..];
+        let start = self.offset + (rest.len() - rest.trim_start().len());
+        let rest = &self.input[start..];
+        let len = match rest.chars().next() {
+            None => return Ok(None),
+            Some('(' | ')') => 1,
+            Some('"') => {
+                let mut chars = rest.char_indices().skip(1);
+                let mut end = None;
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => {
+                            end = Some(i + 1);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                end.ok_or_else(|| SexprError {
+                    message: "unterminated string".to_string(),
+                    offset: start,
+                })?
+            }
+            Some(_) => rest
+                .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+                .unwrap_or(rest.len()),
+        };
+        self.offset = start + len;
+        Ok(Some((&rest[..len], start)))
+    }
+
+    fn expect_next(&mut self) -> Result<(&'a str, usize), SexprError> {
+        self.next()?.ok_or_else(|| SexprError {
+            message: "unexpected end of input".to_string(),
+            offset: self.input.len(),
+        })
+    }
+
+    fn id(&mut self) -> NodeId {
+        self.next_id += 1;
+        NodeId(self.next_id - 1)
+    }
+
+    fn expr(&mut self) -> Result<Expr, SexprError> {
+        let (atom, offset) = self.expect_next()?;
+        match atom {
+            "(" if self.depth >= DEFAULT_MAX_DEPTH => Err(SexprError {
+                message: "too deeply nested expression".to_string(),
+                offset,
+            }),
+            "(" => {
+                self.depth += 1;
+                let expr = self.list();
+                self.depth -= 1;
+                expr
+            }
+            ")" => Err(SexprError {
+                message: "unexpected ')'".to_string(),
+                offset,
+            }),
+            "<error>" => Ok(Expr::Error(self.id())),
+            atom => {
+                let value = match atom {
+                    "nil" => Literal::Nil,
+                    "true" => Literal::Bool(true),
+                    "false" => Literal::Bool(false),
+                    quoted if quoted.starts_with('"') => Literal::String(unescape(quoted, offset)?),
+                    atom => match atom.parse() {
+                        Ok(number) => Literal::Number(number),
+                        Err(_) => {
+                            return Err(SexprError {
+                                message: format!("unknown atom '{atom}'"),
+                                offset,
+                            })
+                        }
+                    },
+                };
+                Ok(Expr::Literal(LiteralExpr {
+                    id: self.id(),
+                    value,
+                }))
+            }
+        }
+    }
+
+    /// The rest of a list, after its opening parenthesis.
+    fn list(&mut self) -> Result<Expr, SexprError> {
+        let (head, offset) = self.expect_next()?;
+        let mut operands = Vec::new();
+        loop {
+            let before = self.offset;
+            if let Some((")", _)) = self.next()? {
+                break;
+            }
+            self.offset = before;
+            operands.push(Box::new(self.expr()?));
+        }
+
+        let binary = binary_operator(head);
+        let expr = match (head, operands.len()) {
+            ("group", 1) => Expr::Grouping(GroupingExpr {
+                id: self.id(),
+                expr: operands.remove(0),
+            }),
+            ("!" | "-", 1) => Expr::Unary(UnaryExpr {
+                id: self.id(),
+                unary: if head == "!" {
+                    Unary::Bang
+                } else {
+                    Unary::Minus
+                },
+                expr: operands.remove(0),
+            }),
+            (_, 2) if binary.is_some() => {
+                let right = operands.pop().unwrap();
+                let left = operands.pop().unwrap();
+                Expr::Binary(BinaryExpr {
+                    id: self.id(),
+                    left,
+                    operator: binary.unwrap(),
+                    right,
+                })
+            }
+            (head, count) => {
+                let message = if binary.is_some() || matches!(head, "group" | "!" | "-") {
+                    format!("'{head}' does not take {count} operand(s)")
+                } else {
+                    format!("unknown operator '{head}'")
+                };
+                return Err(SexprError { message, offset });
+            }
+        };
+        Ok(expr)
+    }
+}
+
+/// The contents of a quoted string atom, with its escapes replaced.
+fn unescape(quoted: &str, offset: usize) -> Result<String, SexprError> {
+    let mut contents = String::new();
+    let mut chars = quoted[1..quoted.len() - 1].char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            contents.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some((_, '"')) => contents.push('"'),
+            Some((_, '\\')) => contents.push('\\'),
+            Some((_, 'n')) => contents.push('\n'),
+            escape => {
+                return Err(SexprError {
+                    message: format!(
+                        "unknown escape '\\{}' in string",
+                        escape.map_or(String::new(), |(_, c)| c.to_string())
+                    ),
+                    offset: offset + 1 + i,
+                })
+            }
+        }
+    }
+    Ok(contents)
+}
+
+fn binary_operator(operator: &str) -> Option<Operator> {
+    let operator = match operator {
+        ">" => Operator::Greater,
+        ">=" => Operator::GreaterEqual,
+        "<" => Operator::Less,
+        "<=" => Operator::LessEqual,
+        "==" => Operator::Equal,
+        "!=" => Operator::NotEqual,
+        "-" => Operator::Minus,
+        "+" => Operator::Plus,
+        "/" => Operator::Divide,
+        "*" => Operator::Multiply,
+        _ => return None,
+    };
+    Some(operator)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        expression::{Expr, Literal, LiteralExpr, NodeId},
+        parse::{Parser, DEFAULT_MAX_DEPTH},
+        pretty_printing::AstPrint,
+        random::Generator,
+        scanner::scan_tokens,
+    };
+
+    use super::{parse, SexprError};
+
+    #[test]
+    fn round_trip() {
+        for source in [
+            "1 + 2 * 3",
+            "-(1 - 2) / !true == nil",
+            "\"foo\" != (((false)))",
+            "1 >= 2.5 < 3 <= 4 > 5",
+            "\"\" + 1",
+            "\"a b\" + \"(c)\"",
+            "\"1\" == \"nil\" != \"true\"",
+            "\"<error>\" + \"\\n\"",
+        ] {
+            let expr = Parser::new(scan_tokens(source).unwrap())
+                .parse_expression()
+                .unwrap();
+            assert_eq!(parse(&expr.print_ast()), Ok(expr));
+        }
+    }
+
+    #[test]
+    fn random_round_trip() {
+        for seed in 0..1000 {
+            let expr = Generator::new(seed).expr(6);
+            // Debug output tells -0.0 from 0.0 apart, and NaN equals itself there
+            let read = parse(&expr.print_ast()).unwrap();
+            assert_eq!(format!("{read:?}"), format!("{expr:?}"), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = |depth| format!("{}1{}", "(group ".repeat(depth), ")".repeat(depth));
+        assert!(parse(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse(&nested(100_000)),
+            Err(SexprError {
+                message: "too deeply nested expression".to_string(),
+                offset: DEFAULT_MAX_DEPTH * "(group ".len(),
+            })
+        );
+    }
+
+    #[test]
+    fn atoms() {
+        let literal = |input| match parse(input).unwrap() {
+            Expr::Literal(LiteralExpr { value, .. }) => value,
+            expr => panic!("expected a literal, got {expr:?}"),
+        };
+        assert_eq!(literal("  12.5 "), Literal::Number(12.5));
+        assert_eq!(literal("nil"), Literal::Nil);
+        assert_eq!(literal("\"nil\""), Literal::String("nil".to_string()));
+        assert_eq!(literal("\"1 (2)\""), Literal::String("1 (2)".to_string()));
+        assert_eq!(
+            literal(r#""say \"hi\"\n\\""#),
+            Literal::String("say \"hi\"\n\\".to_string())
+        );
+        assert_eq!(parse("<error>"), Ok(Expr::Error(NodeId(0))));
+        assert_eq!(
+            parse("(+ (- 1) (group <error>))").unwrap().print_ast(),
+            "(+ (- 1) (group <error>))"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |input| parse(input).unwrap_err().to_string();
+        assert_eq!(error("(+ 1"), "Error at offset 4: unexpected end of input");
+        assert_eq!(
+            error("(+ 1 2) 3"),
+            "Error at offset 8: unexpected '3' after the expression"
+        );
+        assert_eq!(error("(% 1 2)"), "Error at offset 1: unknown operator '%'");
+        assert_eq!(
+            error("(group 1 2)"),
+            "Error at offset 1: 'group' does not take 2 operand(s)"
+        );
+        assert_eq!(
+            error("(- 1 2 3)"),
+            "Error at offset 1: '-' does not take 3 operand(s)"
+        );
+        assert_eq!(error(")"), "Error at offset 0: unexpected ')'");
+        assert_eq!(error("\"foo"), "Error at offset 0: unterminated string");
+        assert_eq!(error(r#""foo\""#), "Error at offset 0: unterminated string");
+        assert_eq!(error("(+ 1 foo)"), "Error at offset 5: unknown atom 'foo'");
+        assert_eq!(
+            error(r#""a\tb""#),
+            "Error at offset 2: unknown escape '\\t' in string"
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Interned names. A `Symbol` is a small copyable handle, so identifiers can be
+//! stored, compared and hashed without touching their text.
+//!
+//! Symbols come from an `Interner`, which belongs to whoever scans the source,
+//! e.g. a compilation or a REPL session, and frees its names when dropped.
+//! Symbols from different interners must not be mixed.
+
+use std::{collections::HashMap, sync::Arc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// Each distinct name is allocated once, and shared by the lookup table and
+/// the list of names.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    symbols: HashMap<Arc<str>, Symbol>,
+    names: Vec<Arc<str>>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.names.len()).expect("too many distinct names"));
+        let name: Arc<str> = name.into();
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// The name of a symbol from this interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interner;
+
+    #[test]
+    fn interning() {
+        let mut interner = Interner::default();
+        let foo = interner.intern("foo");
+        assert_eq!(interner.intern(&String::from("foo")), foo);
+        let bar = interner.intern("bar");
+        assert_ne!(bar, foo);
+        assert_eq!(interner.resolve(foo), "foo");
+        assert_eq!(interner.resolve(bar), "bar");
+    }
+}
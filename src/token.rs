@@ -1,11 +1,48 @@
+use std::ops::Range;
+
+use crate::{scanner::ScanErrorKind, symbol::Symbol};
+
 #[derive(Debug, Clone, PartialEq)]
-pub struct Token {
+pub struct Token<'a> {
     pub ty: TokenType,
+    /// The source text the token was scanned from.
+    pub lexeme: &'a str,
+    /// Byte offsets of the lexeme in the source.
+    pub span: Range<usize>,
     pub line: usize,
+    /// Whitespace and comments preceding the token.
+    /// Only recorded when scanning with `TokenStream::with_trivia`.
+    pub trivia: Vec<Trivia<'a>>,
+}
+
+impl<'a> Token<'a> {
+    /// The text between the quotes of a string literal token,
+    /// `None` for other tokens.
+    pub fn string_contents(&self) -> Option<&'a str> {
+        if self.ty != TokenType::String {
+            return None;
+        }
+        self.lexeme.strip_prefix('"')?.strip_suffix('"')
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+    Shebang,
+    /// Input skipped after a scan error.
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia<'a> {
+    pub kind: TriviaKind,
+    pub text: &'a str,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, parse_display::Display, parse_display::FromStr, PartialEq)]
+#[derive(Debug, Clone, parse_display::Display, PartialEq)]
 #[display(style = "lowercase")]
 pub enum TokenType {
     // Single-character tokens.
@@ -53,10 +90,10 @@ pub enum TokenType {
     // Literals,
     #[display("{0}")]
     Number(f64),
-    #[display("\"{0}\"")]
-    String(String),
-    #[display("{0}")]
-    Identifier(String),
+    /// The contents are the lexeme without its quotes, see `Token::string_contents`.
+    String,
+    #[display("identifier")]
+    Identifier(Symbol),
 
     // Keywords,
     And,
@@ -76,13 +113,60 @@ pub enum TokenType {
     Var,
     While,
 
-    // A never matching regex
-    #[from_str(regex = "$.")]
+    /// Input that could not be scanned, see `TokenStream::with_error_tokens`.
+    #[display("{0}")]
+    Error(ScanErrorKind),
+
     Eof,
 }
 
+impl TokenType {
+    pub fn keyword(word: &str) -> Option<TokenType> {
+        let keyword = match word {
+            "and" => TokenType::And,
+            "class" => TokenType::Class,
+            "else" => TokenType::Else,
+            "false" => TokenType::False,
+            "fun" => TokenType::Fun,
+            "for" => TokenType::For,
+            "if" => TokenType::If,
+            "nil" => TokenType::Nil,
+            "or" => TokenType::Or,
+            "print" => TokenType::Print,
+            "return" => TokenType::Return,
+            "super" => TokenType::Super,
+            "this" => TokenType::This,
+            "true" => TokenType::True,
+            "var" => TokenType::Var,
+            "while" => TokenType::While,
+            _ => return None,
+        };
+        Some(keyword)
+    }
+}
+
+#[test]
+fn keywords() {
+    assert_eq!(TokenType::keyword("while"), Some(TokenType::While));
+    assert_eq!(TokenType::keyword("While"), None);
+    assert_eq!(TokenType::While.to_string(), "while");
+    assert_eq!(TokenType::BangEqual.to_string(), "!=");
+}
+
 #[test]
-fn parsing() {
-    assert_eq!("!=".parse(), Ok(TokenType::BangEqual));
-    assert_eq!("!=123".parse(), Ok(TokenType::BangEqual));
+fn string_contents() {
+    let token = |ty, lexeme| Token {
+        ty,
+        lexeme,
+        span: 0..lexeme.len(),
+        line: 0,
+        trivia: Vec::new(),
+    };
+    assert_eq!(
+        token(TokenType::String, "\"a b\"").string_contents(),
+        Some("a b")
+    );
+    assert_eq!(token(TokenType::String, "\"\"").string_contents(), Some(""));
+    assert_eq!(token(TokenType::String, "\"").string_contents(), None);
+    assert_eq!(token(TokenType::Plus, "+").string_contents(), None);
 }
@@ -0,0 +1,352 @@
+//! Turns an AST back into Lox source code. Parentheses are added wherever the
+//! precedence of the operators requires them, so the output parses back into
+//! the same tree even if it was built or rewritten by hand. The exception are
+//! negative numbers: Lox has no negative literals, so they are written as a
+//! negation, which parses back into a `Unary` node with the same value.
+
+use std::fmt::{self, Write};
+
+use crate::{
+    expression::{Expr, Literal, LiteralExpr, NodeId, Unary},
+    parse::Precedence,
+};
+
+/// A node of the tree that has no valid source form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnparseError {
+    /// An error node stands for source that failed to parse.
+    ErrorNode(NodeId),
+    /// A number literal that is NaN, which no Lox literal can express.
+    NaN(NodeId),
+    /// A string literal containing `"` or a line break. Lox strings have no
+    /// escape sequences and end at the end of the line.
+    String(NodeId),
+}
+
+impl fmt::Display for UnparseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnparseError::ErrorNode(id) => write!(f, "cannot unparse the error node {}", id.0),
+            UnparseError::NaN(id) => write!(f, "cannot unparse NaN in node {}", id.0),
+            UnparseError::String(id) => write!(
+                f,
+                "cannot unparse a string with a quote or line break in node {}",
+                id.0
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UnparseError {}
+
+pub fn unparse(expr: &Expr) -> Result<String, UnparseError> {
+    let mut unparser = Unparser::default();
+    unparser.expr(expr)?;
+    Ok(unparser.output)
+}
+
+/// Like `unparse`, but produces the shortest source it can: no whitespace,
+/// only the parentheses the precedence requires and the shortest form of numbers.
+pub fn unparse_minified(expr: &Expr) -> Result<String, UnparseError> {
+    let mut unparser = Unparser {
+        minify: true,
+        ..Default::default()
+    };
+    unparser.expr(expr)?;
+    Ok(unparser.output)
+}
+
+#[derive(Default)]
+struct Unparser {
+    output: String,
+    minify: bool,
+}
+
+impl Unparser {
+    /// How tightly the expression binds, `None` if it never needs parentheses.
+    fn precedence(&self, expr: &Expr) -> Option<Precedence> {
+        match expr {
+            Expr::Binary(v) => Some(Precedence::of(v.operator)),
+            Expr::Unary(_) => Some(Precedence::Unary),
+            // Minified groupings are written only where they are needed
+            Expr::Grouping(v) if self.minify => self.precedence(&v.expr),
+            // Negative numbers are written as a negation
+            Expr::Literal(LiteralExpr {
+                value: Literal::Number(v),
+                ..
+            }) if v.is_sign_negative() => Some(Precedence::Unary),
+            Expr::Grouping(_) | Expr::Literal(_) | Expr::Error(_) => None,
+        }
+    }
+
+    /// Writes `expr`, parenthesized if it binds looser than `min`.
+    fn operand(&mut self, expr: &Expr, min: Precedence) -> Result<(), UnparseError> {
+        if self
+            .precedence(expr)
+            .is_some_and(|precedence| precedence < min)
+        {
+            self.output.push('(');
+            self.expr(expr)?;
+            self.output.push(')');
+            Ok(())
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<(), UnparseError> {
+        match expr {
+            Expr::Grouping(v) if self.minify => self.expr(&v.expr)?,
+            Expr::Grouping(v) => {
+                self.output.push('(');
+                self.expr(&v.expr)?;
+                self.output.push(')');
+            }
+            Expr::Unary(v) => {
+                self.output.push_str(match v.unary {
+                    Unary::Bang => "!",
+                    Unary::Minus => "-",
+                });
+                self.operand(&v.expr, Precedence::Unary)?;
+            }
+            Expr::Binary(v) => {
+                let precedence = Precedence::of(v.operator);
+                self.operand(&v.left, precedence)?;
+                if self.minify {
+                    self.write(format_args!("{}", v.operator));
+                } else {
+                    self.write(format_args!(" {} ", v.operator));
+                }
+                // Operators are left-associative, so an equally tight right operand needs parentheses
+                self.operand(&v.right, precedence.next())?;
+            }
+            Expr::Literal(v) => self.literal(v)?,
+            Expr::Error(id) => return Err(UnparseError::ErrorNode(*id)),
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &LiteralExpr) -> Result<(), UnparseError> {
+        match &literal.value {
+            Literal::Nil => self.output.push_str("nil"),
+            Literal::Bool(v) => self.write(format_args!("{v}")),
+            Literal::Number(v) if v.is_nan() => return Err(UnparseError::NaN(literal.id)),
+            // Lox has no literal for infinity, but an overflowing one evaluates to it
+            Literal::Number(v) if v.is_infinite() => {
+                self.output
+                    .push_str(if *v < 0.0 { "-1e999" } else { "1e999" })
+            }
+            Literal::Number(v) => {
+                let decimal = v.to_string();
+                let exponent = format!("{v:e}");
+                if self.minify && exponent.len() < decimal.len() {
+                    self.output.push_str(&exponent);
+                } else {
+                    self.output.push_str(&decimal);
+                }
+            }
+            Literal::String(v) if v.contains(['"', '\n']) => {
+                return Err(UnparseError::String(literal.id))
+            }
+            Literal::String(v) => self.write(format_args!("\"{v}\"")),
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, args: fmt::Arguments<'_>) {
+        self.output
+            .write_fmt(args)
+            .expect("writing to a string cannot fail");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        diff::diff,
+        expression::{BinaryExpr, Expr, Literal, LiteralExpr, NodeId, Operator, Unary, UnaryExpr},
+        parse::Parser,
+        random::Generator,
+        scanner::scan_tokens,
+        sexpr,
+    };
+
+    use super::{unparse, unparse_minified, UnparseError};
+
+    fn parse(source: &str) -> Expr {
+        Parser::new(scan_tokens(source).unwrap())
+            .parse_expression()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        for source in [
+            "1 + 2 * 3",
+            "(1 + 2) * 3",
+            "1 - 2 - 3",
+            "-(1 - 2) / !true",
+            "--1 == !!nil != \"foo\"",
+            "1 < 2 == 3 >= 4.5",
+            "1e999",
+        ] {
+            assert_eq!(unparse(&parse(source)).unwrap(), source);
+        }
+    }
+
+    #[test]
+    fn parenthesizes_by_precedence() {
+        let number = |value| {
+            Box::new(Expr::Literal(LiteralExpr {
+                id: NodeId(0),
+                value: Literal::Number(value),
+            }))
+        };
+        let binary = |left, operator, right| {
+            Box::new(Expr::Binary(BinaryExpr {
+                id: NodeId(0),
+                left,
+                operator,
+                right,
+            }))
+        };
+
+        let sum = binary(number(1.0), Operator::Plus, number(2.0));
+        let expr = binary(sum.clone(), Operator::Multiply, number(3.0));
+        assert_eq!(unparse(&expr).unwrap(), "(1 + 2) * 3");
+
+        let expr = binary(number(3.0), Operator::Minus, sum.clone());
+        assert_eq!(unparse(&expr).unwrap(), "3 - (1 + 2)");
+
+        let expr = binary(sum.clone(), Operator::Minus, number(3.0));
+        assert_eq!(unparse(&expr).unwrap(), "1 + 2 - 3");
+
+        let expr = Expr::Unary(UnaryExpr {
+            id: NodeId(0),
+            unary: Unary::Minus,
+            expr: sum,
+        });
+        let source = unparse(&expr).unwrap();
+        assert_eq!(source, "-(1 + 2)");
+        assert_eq!(unparse(&parse(&source)).unwrap(), source);
+    }
+
+    /// The tree the unparsed source of `expr` parses into, up to groupings:
+    /// negative numbers are written as negations. `None` if it contains NaN or
+    /// a string that Lox cannot express.
+    fn expected(expr: &Expr) -> Option<Expr> {
+        Some(match expr {
+            Expr::Literal(LiteralExpr {
+                value: Literal::Number(v),
+                ..
+            }) if v.is_nan() => return None,
+            Expr::Literal(LiteralExpr {
+                value: Literal::String(v),
+                ..
+            }) if v.contains(['"', '\n']) => return None,
+            Expr::Literal(LiteralExpr {
+                id,
+                value: Literal::Number(v),
+            }) if v.is_sign_negative() => Expr::Unary(UnaryExpr {
+                id: *id,
+                unary: Unary::Minus,
+                expr: Box::new(Expr::Literal(LiteralExpr {
+                    id: *id,
+                    value: Literal::Number(-v),
+                })),
+            }),
+            Expr::Grouping(v) => expected(&v.expr)?,
+            Expr::Unary(v) => Expr::Unary(UnaryExpr {
+                expr: Box::new(expected(&v.expr)?),
+                ..v.clone()
+            }),
+            Expr::Binary(v) => Expr::Binary(BinaryExpr {
+                left: Box::new(expected(&v.left)?),
+                right: Box::new(expected(&v.right)?),
+                ..v.clone()
+            }),
+            Expr::Literal(_) | Expr::Error(_) => expr.clone(),
+        })
+    }
+
+    #[test]
+    fn random_round_trip() {
+        for seed in 0..1000 {
+            let expr = Generator::new(seed).expr(6);
+            let Some(expected) = expected(&expr) else {
+                assert!(
+                    matches!(
+                        unparse(&expr),
+                        Err(UnparseError::NaN(_) | UnparseError::String(_))
+                    ),
+                    "seed {seed}: an unrepresentable literal was unparsed"
+                );
+                continue;
+            };
+            for source in [unparse(&expr).unwrap(), unparse_minified(&expr).unwrap()] {
+                let parsed = parse(&source);
+                let changes = diff(&expected, &parsed);
+                assert!(
+                    changes.is_empty(),
+                    "seed {seed}: {source} changed {changes:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn minified() {
+        let minify = |source| unparse_minified(&parse(source)).unwrap();
+        assert_eq!(minify("(1 + 2) * (3) // three"), "(1+2)*3");
+        assert_eq!(minify("((1 - 2)) - (3 - (4))"), "1-2-(3-4)");
+        assert_eq!(minify("- (-1) == ! (true)"), "--1==!true");
+        assert_eq!(minify("1000000 + 0.0001 + 1.5"), "1e6+1e-4+1.5");
+        assert_eq!(minify("\"a  b\" != nil"), "\"a  b\"!=nil");
+
+        let source = "-(1 + 2) / (3 * 4) < 5";
+        let minified = minify(source);
+        assert_eq!(minified, "-(1+2)/(3*4)<5");
+        assert_eq!(minify(&minified), minified);
+    }
+
+    #[test]
+    fn error_node() {
+        let mut parser = Parser::new(scan_tokens("1 + )").unwrap()).with_recovery();
+        let expr = parser.parse_expression().unwrap();
+        assert_eq!(unparse(&expr), Err(UnparseError::ErrorNode(NodeId(1))));
+    }
+
+    #[test]
+    fn strings() {
+        let string = |value: &str| {
+            Expr::Literal(LiteralExpr {
+                id: NodeId(0),
+                value: Literal::String(value.to_string()),
+            })
+        };
+        assert_eq!(unparse(&string("")).unwrap(), "\"\"");
+        assert_eq!(unparse(&string("a (b) // c")).unwrap(), "\"a (b) // c\"");
+        for value in ["say \"hi\"", "two\nlines"] {
+            assert_eq!(
+                unparse(&string(value)),
+                Err(UnparseError::String(NodeId(0)))
+            );
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        let unparse_sexpr = |input| unparse(&sexpr::parse(input).unwrap());
+        assert_eq!(unparse_sexpr("(- 2 -1)").unwrap(), "2 - -1");
+        assert_eq!(unparse_sexpr("(- -0)").unwrap(), "--0");
+        assert_eq!(unparse_sexpr("(* -inf inf)").unwrap(), "-1e999 * 1e999");
+        assert_eq!(
+            unparse_sexpr("(+ 1 NaN)"),
+            Err(UnparseError::NaN(NodeId(1)))
+        );
+        assert_eq!(
+            unparse_minified(&sexpr::parse("(- 2 -1e-9)").unwrap()).unwrap(),
+            "2--1e-9"
+        );
+    }
+}